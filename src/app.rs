@@ -1,9 +1,24 @@
+use crate::autosave::{
+    AUTOSAVE_DELAY, AutoSave, AutoSaveTimer, DocumentSnapshot, RestoredSession, SessionRevision,
+    SessionSnapshot,
+};
 use crate::canvas_items::*;
-use crate::drawing::{ItemRenderer, PreviewRenderer, ShapeFactory};
-use crate::export::{DownloadHandler, ImageExporter};
-use crate::state::{DrawingState, SelectionState, UiState};
+use crate::clipboard::ItemClipboard;
+use crate::document::{Document, SourceFile};
+use crate::drawing::{HandleAction, ItemRenderer, PreviewRenderer, ShapeFactory};
+use crate::drawing_tool::DrawingTool;
+use crate::error::AppError;
+use crate::export::{
+    BATCH_ZIP_FILENAME, BatchExportJob, COCO_FILENAME, DatasetFormat, DownloadHandler,
+    ExportPreview, ExportProgress, FileNameTemplate, ImageExportJob, ImageExporter,
+};
+use crate::keymap::{KEYMAP_STORAGE_KEY, KeyMap, ShortcutAction};
+use crate::state::{
+    DrawingState, EyedropperTarget, PREFERENCES_STORAGE_KEY, ToolPreferences, UiState,
+};
 use crate::touch_handler::get_current_touches;
-use crate::ui;
+use crate::ui::{self, ExportRequest, PresetFileAction, TabAction};
+use crate::viewport::Viewport;
 use egui::{FontData, FontDefinitions, FontFamily};
 use js_sys;
 use once_cell::sync::Lazy;
@@ -11,33 +26,93 @@ use std::sync::{Arc, Mutex};
 use wasm_bindgen::prelude::*;
 use web_sys::{File, FileReader, HtmlInputElement};
 
+/// 読み込みが完了し、ドキュメント化を待っている画像
+struct PendingImage {
+    source: SourceFile,
+    bytes: Vec<u8>,
+}
+
 struct AppState {
-    image_bytes: Option<Vec<u8>>,
+    pending_images: Vec<PendingImage>,
+    // 読み込んだプリセットファイルの内容
+    pending_preset_file: Option<String>,
+    // 読み込んだ COCO / Pascal VOC ファイルの内容
+    pending_annotation_file: Option<String>,
+    // ファイル読み込みのコールバックで発生したエラー
+    pending_errors: Vec<AppError>,
 }
 
-static APP_STATE: Lazy<Arc<Mutex<AppState>>> =
-    Lazy::new(|| Arc::new(Mutex::new(AppState { image_bytes: None })));
+static APP_STATE: Lazy<Arc<Mutex<AppState>>> = Lazy::new(|| {
+    Arc::new(Mutex::new(AppState {
+        pending_images: Vec::new(),
+        pending_preset_file: None,
+        pending_annotation_file: None,
+        pending_errors: Vec::new(),
+    }))
+});
+
+/// 実行中のエクスポート
+enum ExportTask {
+    Image {
+        job: ImageExportJob,
+        document_id: u64,
+        filename: String,
+        format: String,
+    },
+    Zip {
+        job: BatchExportJob,
+        document_ids: Vec<u64>,
+    },
+}
+
+impl ExportTask {
+    fn progress(&self) -> ExportProgress {
+        match self {
+            ExportTask::Image { job, .. } => ExportProgress {
+                fraction: job.progress(),
+                label: job.stage_label().to_string(),
+            },
+            ExportTask::Zip { job, .. } => job.progress(),
+        }
+    }
+}
 
 pub struct AnnotoApp {
-    image_texture: Option<egui::TextureHandle>,
-    image_bytes: Option<Vec<u8>>,
-    rectangles: Vec<CanvasItem>,
+    // 開いている画像（タブ）
+    documents: Vec<Document>,
+    active: usize,
+    next_document_id: u64,
 
     // State management
     drawing_state: DrawingState,
     ui_state: UiState,
-    selection_state: SelectionState,
+    keymap: KeyMap,
+    clipboard: ItemClipboard,
+
+    // 自動保存
+    autosave_timer: AutoSaveTimer,
+    session_loaded: bool,
+    restorable_session: Option<RestoredSession>,
+
+    export_task: Option<ExportTask>,
+    export_preview: ExportPreview,
 }
 
 impl Default for AnnotoApp {
     fn default() -> Self {
         Self {
-            image_texture: None,
-            image_bytes: None,
-            rectangles: Vec::new(),
+            documents: Vec::new(),
+            active: 0,
+            next_document_id: 0,
             drawing_state: DrawingState::default(),
             ui_state: UiState::default(),
-            selection_state: SelectionState::default(),
+            keymap: KeyMap::default(),
+            clipboard: ItemClipboard::default(),
+            autosave_timer: AutoSaveTimer::default(),
+            session_loaded: false,
+            restorable_session: None,
+            export_task: None,
+            export_preview: ExportPreview::default(),
         }
     }
 }
@@ -70,11 +145,14 @@ impl AnnotoApp {
         let input: HtmlInputElement = input.dyn_into().unwrap();
         input.set_type("file");
         input.set_accept("image/*");
+        input.set_multiple(true);
         let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
             let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
             if let Some(files) = input.files() {
-                if let Some(file) = files.get(0) {
-                    Self::load_image(file);
+                for i in 0..files.length() {
+                    if let Some(file) = files.get(i) {
+                        Self::load_image(file);
+                    }
                 }
             }
         }) as Box<dyn FnMut(_)>);
@@ -87,13 +165,28 @@ impl AnnotoApp {
 
     fn load_image(file: File) {
         let reader = FileReader::new().unwrap();
+        let source = SourceFile {
+            name: file.name(),
+            mime_type: file.type_(),
+            size_bytes: file.size() as u64,
+            last_modified: file.last_modified(),
+        };
         let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
             let reader: FileReader = event.target().unwrap().dyn_into().unwrap();
-            if let Ok(result) = reader.result() {
-                let array_buffer = js_sys::ArrayBuffer::from(result);
-                let uint8_array = js_sys::Uint8Array::new(&array_buffer);
-                let bytes = uint8_array.to_vec();
-                APP_STATE.lock().unwrap().image_bytes = Some(bytes);
+            let mut app_state = APP_STATE.lock().unwrap();
+            match reader.result() {
+                Ok(result) => {
+                    let array_buffer = js_sys::ArrayBuffer::from(result);
+                    let uint8_array = js_sys::Uint8Array::new(&array_buffer);
+                    let bytes = uint8_array.to_vec();
+                    app_state.pending_images.push(PendingImage {
+                        source: source.clone(),
+                        bytes,
+                    });
+                }
+                Err(_) => app_state.pending_errors.push(AppError::Load {
+                    name: source.name.clone(),
+                }),
             }
         }) as Box<dyn FnMut(_)>);
         reader
@@ -103,183 +196,684 @@ impl AnnotoApp {
         reader.read_as_array_buffer(&file).unwrap();
     }
 
+    fn open_preset_file_dialog() {
+        Self::open_text_file_dialog(".json,application/json", |text| {
+            APP_STATE.lock().unwrap().pending_preset_file = Some(text);
+        });
+    }
+
+    fn open_annotation_file_dialog() {
+        Self::open_text_file_dialog(".json,.xml,application/json,application/xml", |text| {
+            APP_STATE.lock().unwrap().pending_annotation_file = Some(text);
+        });
+    }
+
+    /// テキストファイルを1つ選択させ、読み込んだ内容を渡す
+    fn open_text_file_dialog(accept: &str, on_text: fn(String)) {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let input = document.create_element("input").unwrap();
+        let input: HtmlInputElement = input.dyn_into().unwrap();
+        input.set_type("file");
+        input.set_accept(accept);
+        let closure = Closure::wrap(Box::new(move |event: web_sys::Event| {
+            let input: HtmlInputElement = event.target().unwrap().dyn_into().unwrap();
+            let Some(file) = input.files().and_then(|files| files.get(0)) else {
+                return;
+            };
+            let reader = FileReader::new().unwrap();
+            let on_load = Closure::wrap(Box::new(move |event: web_sys::Event| {
+                let reader: FileReader = event.target().unwrap().dyn_into().unwrap();
+                if let Some(text) = reader.result().ok().and_then(|result| result.as_string()) {
+                    on_text(text);
+                }
+            }) as Box<dyn FnMut(_)>);
+            reader
+                .add_event_listener_with_callback("load", on_load.as_ref().unchecked_ref())
+                .unwrap();
+            on_load.forget();
+            reader.read_as_text(&file).unwrap();
+        }) as Box<dyn FnMut(_)>);
+        input
+            .add_event_listener_with_callback("change", closure.as_ref().unchecked_ref())
+            .unwrap();
+        closure.forget();
+        input.click();
+    }
+
     pub fn new(cc: &eframe::CreationContext<'_>) -> Self {
         Self::setup_fonts(&cc.egui_ctx);
-        Default::default()
+        // Ctrl +/-/0 は画像の倍率変更に使うため egui の UI 拡大縮小を無効化
+        cc.egui_ctx.options_mut(|o| o.zoom_with_keyboard = false);
+
+        let mut app = Self::default();
+        if let Some(keymap) = cc
+            .storage
+            .and_then(|storage| eframe::get_value::<KeyMap>(storage, KEYMAP_STORAGE_KEY))
+        {
+            app.keymap = keymap.with_missing_defaults();
+        }
+        if let Some(preferences) = cc.storage.and_then(|storage| {
+            eframe::get_value::<ToolPreferences>(storage, PREFERENCES_STORAGE_KEY)
+        }) {
+            app.drawing_state = DrawingState::from_preferences(preferences);
+        }
+        AutoSave::load();
+        app
+    }
+
+    fn active_document(&self) -> Option<&Document> {
+        self.documents.get(self.active)
+    }
+
+    fn active_document_mut(&mut self) -> Option<&mut Document> {
+        self.documents.get_mut(self.active)
+    }
+
+    /// 表示するドキュメントを切り替える（表示位置はドキュメントごとに保持）
+    fn activate_document(&mut self, index: usize) {
+        if index >= self.documents.len() {
+            return;
+        }
+        if index != self.active {
+            let viewport = self.ui_state.viewport;
+            let view_command = self.ui_state.view_command.take();
+            if let Some(current) = self.active_document_mut() {
+                current.viewport = viewport;
+                current.view_command = view_command;
+            }
+        }
+        self.active = index;
+        let next = &mut self.documents[index];
+        self.ui_state.viewport = next.viewport;
+        self.ui_state.view_command = next.view_command.take();
+        self.drawing_state.drag_start = None;
+    }
+
+    fn close_document(&mut self, index: usize) {
+        if index >= self.documents.len() {
+            return;
+        }
+        if index == self.active {
+            self.documents.remove(index);
+            self.active = index.min(self.documents.len().saturating_sub(1));
+            if let Some(next) = self.documents.get_mut(self.active) {
+                self.ui_state.viewport = next.viewport;
+                self.ui_state.view_command = next.view_command.take();
+            }
+            self.drawing_state.drag_start = None;
+        } else {
+            self.documents.remove(index);
+            if index < self.active {
+                self.active -= 1;
+            }
+        }
+    }
+
+    fn handle_tab_action(&mut self, action: TabAction) {
+        match action {
+            TabAction::Activate(index) => {
+                if index != self.active {
+                    self.activate_document(index);
+                }
+            }
+            TabAction::Close(index) => {
+                // 未保存の変更がある場合は確認してから閉じる
+                let Some(document) = self.documents.get(index) else {
+                    return;
+                };
+                if document.dirty {
+                    self.ui_state.pending_close = Some(document.id);
+                } else {
+                    self.close_document(index);
+                }
+            }
+        }
     }
 }
 
 impl eframe::App for AnnotoApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         self.handle_image_loading(ctx);
+        self.handle_session_restore(ctx);
+        self.handle_preset_import();
+        self.handle_annotation_import();
+
+        ui::render_top_panel(ctx, &mut self.ui_state, Self::open_file_dialog);
+
+        if let Some(action) = ui::render_document_tabs(ctx, &self.documents, self.active) {
+            self.handle_tab_action(action);
+        }
 
-        ui::render_top_panel(
+        let selected_item = self.documents.get(self.active).and_then(|document| {
+            document
+                .selection_state
+                .selected_item()
+                .and_then(|idx| document.rectangles.get(idx))
+        });
+        let mut style_changes = Vec::new();
+        let mut property_changes = Vec::new();
+        let preset_action = ui::render_side_panel(
             ctx,
             &mut self.drawing_state,
             &mut self.ui_state,
-            Self::open_file_dialog,
+            selected_item,
+            |change| style_changes.push(change),
+            |change| property_changes.push(change),
         );
+        self.apply_style_changes(style_changes, property_changes);
+        match preset_action {
+            Some(PresetFileAction::Import) => Self::open_preset_file_dialog(),
+            Some(PresetFileAction::Export) => {
+                let result = DownloadHandler::download_json(
+                    &ToolPreferences::export_presets(&self.drawing_state.presets),
+                    "annoto_presets.json",
+                );
+                if let Err(e) = result {
+                    self.ui_state.notifications.push_error(&e);
+                }
+            }
+            None => {}
+        }
 
-        self.sync_ui_from_selection();
-
-        ui::render_side_panel(
+        let document = self.documents.get(self.active);
+        ui::render_navigator_panel(
             ctx,
-            &mut self.drawing_state,
-            &self.ui_state,
-            self.selection_state.selected_item,
-            &self.rectangles,
-            || {},
+            &mut self.ui_state,
+            document.map(|document| &document.image_texture),
+            document.map_or(&[][..], |document| document.rectangles.as_slice()),
         );
 
         // Render central panel with closures
         self.render_central_panel_with_closures(ctx);
 
-        let should_export =
-            ui::show_export_dialog(ctx, &mut self.ui_state, self.image_bytes.as_ref(), || {});
+        self.step_export(ctx);
+        // エクスポート中はプレビューの作成を止めて出力を優先する
+        if self.export_task.is_none() {
+            self.export_preview
+                .update(ctx, self.documents.get(self.active), &self.ui_state);
+        }
+        let export_progress = self.export_task.as_ref().map(ExportTask::progress);
+        let export_request = ui::show_export_dialog(
+            ctx,
+            &mut self.ui_state,
+            self.documents.get(self.active),
+            self.documents.len(),
+            export_progress.as_ref(),
+            &self.export_preview,
+            || {},
+        );
 
-        if should_export {
-            self.export_image();
+        match export_request {
+            Some(ExportRequest::Current) => self.export_image(),
+            Some(ExportRequest::AllAsZip) => self.export_all_images(),
+            Some(ExportRequest::CocoJson) => self.export_coco(),
+            Some(ExportRequest::PascalVoc) => self.export_pascal_voc(),
+            Some(ExportRequest::ImportAnnotations) => Self::open_annotation_file_dialog(),
+            Some(ExportRequest::Cancel) => self.export_task = None,
+            None => {}
         }
 
-        // Update selected item after UI rendering
-        self.update_selected_item();
+        ui::show_shortcut_help(ctx, &mut self.ui_state, &mut self.keymap);
+        ui::show_notifications(ctx, &mut self.ui_state.notifications);
+
+        let confirmed_close = ui::show_close_confirmation(ctx, &mut self.ui_state, &self.documents)
+            .and_then(|id| self.documents.iter().position(|d| d.id == id));
+        if let Some(index) = confirmed_close {
+            self.close_document(index);
+        }
 
         self.handle_keyboard_events(ctx);
+
+        self.autosave(ctx);
+    }
+
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        eframe::set_value(storage, KEYMAP_STORAGE_KEY, &self.keymap);
+        eframe::set_value(
+            storage,
+            PREFERENCES_STORAGE_KEY,
+            &self.drawing_state.preferences(),
+        );
     }
 }
 
 impl AnnotoApp {
     fn render_central_panel_with_closures(&mut self, ctx: &egui::Context) {
         egui::CentralPanel::default().show(ctx, |ui| {
-            if let Some(texture) = &self.image_texture.clone() {
-                let scale = self.drawing_state.zoom / 100.0;
-
-                egui::ScrollArea::both()
-                    .auto_shrink([false; 2])
-                    .show(ui, |ui| {
-                        let pointer_pos = ui.input(|i| i.pointer.hover_pos());
-
-                        let scaled_size = texture.size_vec2() * scale;
-                        let image_response =
-                            ui.allocate_response(scaled_size, egui::Sense::click_and_drag());
-                        let mut image_rect = image_response.rect;
-
-                        // パンオフセットを適用（View モードと Drawing モード両方で）
-                        image_rect = image_rect.translate(self.ui_state.pan_offset);
-
-                        ui.painter().image(
-                            texture.id(),
-                            image_rect,
-                            egui::Rect::from_min_size(egui::Pos2::ZERO, egui::Vec2::splat(1.0)),
-                            egui::Color32::WHITE,
-                        );
-
-                        if let Some(pos) = pointer_pos {
-                            if image_rect.contains(pos) {
-                                let cursor_in_image = (pos - image_rect.min) / scale;
-                                self.ui_state.cursor_pos =
-                                    Some(egui::Pos2::new(cursor_in_image.x, cursor_in_image.y));
+            let image_size = self
+                .active_document()
+                .map(|document| document.image_texture.size_vec2());
+            if let Some(image_size) = image_size {
+                let canvas_response =
+                    ui.allocate_rect(ui.max_rect(), egui::Sense::click_and_drag());
+                let viewport = &mut self.ui_state.viewport;
+                viewport.canvas_rect = canvas_response.rect;
+                viewport.image_size = image_size;
+                if let Some(command) = self.ui_state.view_command.take() {
+                    viewport.apply_command(command);
+                }
+                let viewport = *viewport;
+                let image_rect = viewport.image_screen_rect();
+
+                let pointer_pos = ui.input(|i| i.pointer.hover_pos());
+
+                if let Some(document) = self.active_document() {
+                    document.image_texture.paint(ui.painter(), image_rect);
+                }
+
+                if let Some(pos) = pointer_pos {
+                    if image_rect.contains(pos) {
+                        self.ui_state.cursor_pos = Some(viewport.screen_to_image(pos));
+                    }
+                } else {
+                    self.ui_state.cursor_pos = None;
+                }
+
+                // タッチイベント処理
+                self.handle_touch_events(&viewport);
+
+                let panning = self.handle_view_panning(ui, &canvas_response);
+                let picking = self.ui_state.eyedropper.is_some();
+                // パン中とスポイト使用中は描画や選択を行わない
+                let canvas_locked = panning || picking;
+                if !canvas_locked {
+                    self.handle_drawing_mode(ui, &canvas_response, &viewport);
+                }
+
+                let document = &mut self.documents[self.active];
+                ItemRenderer::render_existing_items(ui, &document.rectangles, &viewport);
+                PreviewRenderer::render_drag_preview(
+                    ui,
+                    &self.drawing_state,
+                    &viewport,
+                    // 吸着しない間はエッジ強度を計算しない
+                    self.drawing_state
+                        .magnetic_snap
+                        .then(|| document.edge_map()),
+                );
+                ItemRenderer::render_selection_outlines(
+                    ui,
+                    &document.selection_state.selected_items,
+                    &document.rectangles,
+                    &viewport,
+                );
+                let handle_action = ItemRenderer::render_handles(
+                    ui,
+                    document.selection_state.selected_item(),
+                    &mut document.selection_state.selected_handle,
+                    &mut document.rectangles,
+                    &viewport,
+                );
+                match handle_action {
+                    Some(HandleAction::Resized) => document.mark_changed(),
+                    Some(HandleAction::Delete) => document.delete_selected_items(),
+                    None => {}
+                }
+
+                let mut hovering_index = None;
+                if let Some(pos) = pointer_pos {
+                    if image_rect.contains(pos) {
+                        for (i, item) in document.rectangles.iter().enumerate() {
+                            if item.hit_test(pos, &viewport) {
+                                hovering_index = Some(i);
+                                break;
                             }
+                        }
+                        if panning {
+                            ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Grabbing);
+                        } else if picking {
+                            ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Crosshair);
+                        } else if hovering_index.is_some() {
+                            ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Grab);
                         } else {
-                            self.ui_state.cursor_pos = None;
+                            ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Default);
                         }
+                    }
+                }
 
-                        // タッチイベント処理
-                        self.handle_touch_events(image_rect, scale);
-
-                        self.handle_drawing_mode(ui, &image_response, image_rect, scale);
-                        ItemRenderer::render_existing_items(
-                            ui,
-                            &mut self.rectangles,
-                            image_rect,
-                            scale,
-                        );
-                        PreviewRenderer::render_drag_preview(
-                            ui,
-                            &self.drawing_state,
-                            image_rect,
-                            scale,
-                        );
-                        let should_delete = ItemRenderer::render_handles(
-                            ui,
-                            self.selection_state.selected_item,
-                            &mut self.selection_state.selected_handle,
-                            &mut self.rectangles,
-                            image_rect,
-                            scale,
-                        );
-
-                        if should_delete {
-                            if let Some(idx) = self.selection_state.selected_item {
-                                self.rectangles.remove(idx);
-                                self.selection_state.selected_item = None;
-                                self.selection_state.selected_handle = None;
-                            }
-                        }
+                // クラス名・メモ・タグが設定されたアイテムはカーソルを合わせると内容を表示
+                let summary = hovering_index
+                    .filter(|_| !canvas_locked && !canvas_response.dragged())
+                    .and_then(|idx| document.rectangles.get(idx))
+                    .and_then(|item| item.metadata().summary());
+                if let Some(summary) = summary {
+                    canvas_response.clone().on_hover_text_at_pointer(summary);
+                }
 
-                        let mut hovering_index = None;
-                        if let Some(pos) = pointer_pos {
-                            if image_rect.contains(pos) {
-                                for (i, item) in self.rectangles.iter().enumerate() {
-                                    if item.hit_test(pos, image_rect, scale) {
-                                        hovering_index = Some(i);
-                                        break;
-                                    }
-                                }
-                                if hovering_index.is_some() {
-                                    ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Grab);
-                                } else {
-                                    ui.output_mut(|o| o.cursor_icon = egui::CursorIcon::Default);
-                                }
-                            }
-                        }
+                let selection_state = &mut document.selection_state;
+                if canvas_response.clicked() && !canvas_locked {
+                    if let Some(idx) = hovering_index {
+                        selection_state.select(idx);
+                    } else {
+                        selection_state.clear();
+                    }
+                }
 
-                        if image_response.clicked() {
-                            if let Some(idx) = hovering_index {
-                                self.selection_state.selected_item = Some(idx);
-                                self.selection_state.selected_handle = None;
-                            } else {
-                                self.selection_state.selected_item = None;
-                                self.selection_state.selected_handle = None;
-                            }
-                        }
+                if canvas_response.drag_started() && !canvas_locked {
+                    match hovering_index {
+                        // 選択済みのアイテムをドラッグした場合は複数選択を維持する
+                        Some(idx) if selection_state.is_selected(idx) => {}
+                        Some(idx) => selection_state.select(idx),
+                        None => selection_state.clear(),
+                    }
+                }
 
-                        if image_response.drag_started() {
-                            if let Some(idx) = hovering_index {
-                                self.selection_state.selected_item = Some(idx);
-                                self.selection_state.selected_handle = None;
-                            } else {
-                                self.selection_state.selected_item = None;
-                                self.selection_state.selected_handle = None;
-                            }
-                        }
+                if canvas_response.dragged()
+                    && !canvas_locked
+                    && selection_state.selected_handle.is_none()
+                {
+                    let drag_delta = viewport.screen_delta_to_image(canvas_response.drag_delta());
+                    document.translate_selected_items(drag_delta);
+                }
 
-                        if let Some(selected_idx) = self.selection_state.selected_item {
-                            if image_response.dragged()
-                                && self.selection_state.selected_handle.is_none()
-                            {
-                                let drag_delta = image_response.drag_delta() / scale;
-                                if let Some(item) = self.rectangles.get_mut(selected_idx) {
-                                    item.translate(drag_delta);
-                                }
-                            }
-                        }
-                    });
+                if picking {
+                    self.handle_eyedropper(ui, &canvas_response, &viewport);
+                }
+
+                self.handle_wheel(ui, &canvas_response);
             }
         });
     }
 
+    /// スポイト：クリックした位置の画素の色を線または塗りの色にする
+    fn handle_eyedropper(
+        &mut self,
+        ui: &egui::Ui,
+        canvas_response: &egui::Response,
+        viewport: &Viewport,
+    ) {
+        let Some(target) = self.ui_state.eyedropper else {
+            return;
+        };
+        let Some(document) = self.documents.get_mut(self.active) else {
+            return;
+        };
+        let Some(pos) = ui.input(|i| i.pointer.hover_pos()) else {
+            return;
+        };
+        let Some(sampled) = document.image.pixel_at(viewport.screen_to_image(pos)) else {
+            return;
+        };
+
+        // カーソルの横に取得する色を表示
+        let swatch =
+            egui::Rect::from_min_size(pos + egui::vec2(12.0, 12.0), egui::Vec2::splat(20.0));
+        ui.painter().rect_filled(swatch, 2.0, sampled);
+        ui.painter().rect_stroke(
+            swatch,
+            2.0,
+            egui::Stroke::new(1.0, egui::Color32::WHITE),
+            egui::StrokeKind::Outside,
+        );
+
+        if !canvas_response.clicked() {
+            return;
+        }
+        // 不透明度は変更先の色のものを維持する
+        let selected = document
+            .selection_state
+            .selected_item()
+            .and_then(|idx| document.rectangles.get_mut(idx));
+        match selected {
+            Some(item) => {
+                let change = match target {
+                    EyedropperTarget::Stroke => item.get_stroke_color().map(|current| {
+                        StyleChange::StrokeColor(ui::with_alpha_of(sampled, current))
+                    }),
+                    EyedropperTarget::Fill => item
+                        .get_fill_color()
+                        .map(|current| StyleChange::FillColor(ui::with_alpha_of(sampled, current))),
+                };
+                if let Some(change) = change {
+                    item.apply_style_change(change);
+                    document.mark_changed();
+                }
+            }
+            None => {
+                let style = self
+                    .drawing_state
+                    .styles
+                    .get_mut(self.drawing_state.current_tool);
+                match target {
+                    EyedropperTarget::Stroke => {
+                        style.stroke_color = ui::with_alpha_of(sampled, style.stroke_color)
+                    }
+                    EyedropperTarget::Fill => {
+                        style.fill_color = ui::with_alpha_of(sampled, style.fill_color)
+                    }
+                }
+            }
+        }
+        self.drawing_state.remember_color(sampled);
+        self.ui_state.eyedropper = None;
+    }
+
     fn handle_image_loading(&mut self, ctx: &egui::Context) {
-        if let Some(bytes) = APP_STATE.lock().unwrap().image_bytes.take() {
-            if let Ok(img) = image::load_from_memory(&bytes) {
-                let rgba = img.to_rgba8();
-                let size = [rgba.width() as usize, rgba.height() as usize];
-                let pixels = rgba.into_raw();
-                let color_image = egui::ColorImage::from_rgba_unmultiplied(size, &pixels);
-                self.image_texture =
-                    Some(ctx.load_texture("image", color_image, egui::TextureOptions::default()));
-                self.image_bytes = Some(bytes);
+        let (pending, errors) = {
+            let mut app_state = APP_STATE.lock().unwrap();
+            (
+                std::mem::take(&mut app_state.pending_images),
+                std::mem::take(&mut app_state.pending_errors),
+            )
+        };
+        for error in &errors {
+            self.ui_state.notifications.push_error(error);
+        }
+        for image in pending {
+            let id = self.next_document_id;
+            match Document::load(ctx, id, image.source, image.bytes) {
+                Ok(document) => {
+                    self.next_document_id += 1;
+                    self.documents.push(document);
+                    // 最後に読み込んだ画像を表示する
+                    self.activate_document(self.documents.len() - 1);
+                }
+                Err(e) => self.ui_state.notifications.push_error(&e),
+            }
+        }
+    }
+
+    /// 読み込んだプリセットを追加（同じ名前のものは置き換える）
+    fn handle_preset_import(&mut self) {
+        let Some(text) = APP_STATE.lock().unwrap().pending_preset_file.take() else {
+            return;
+        };
+        match ToolPreferences::import_presets(&text) {
+            Ok(presets) => {
+                for preset in presets {
+                    let presets = &mut self.drawing_state.presets;
+                    match presets.iter_mut().find(|p| p.name == preset.name) {
+                        Some(existing) => *existing = preset,
+                        None => presets.push(preset),
+                    }
+                }
+            }
+            Err(reason) => {
+                let error = AppError::PresetImport { reason };
+                self.ui_state.notifications.push_error(&error);
+            }
+        }
+    }
+
+    /// 読み込んだ COCO / Pascal VOC の矩形を、ファイル名が一致する画像に追加する
+    fn handle_annotation_import(&mut self) {
+        let Some(text) = APP_STATE.lock().unwrap().pending_annotation_file.take() else {
+            return;
+        };
+        let images = match DatasetFormat::parse(&text) {
+            Ok(images) => images,
+            Err(reason) => {
+                let error = AppError::AnnotationImport { reason };
+                self.ui_state.notifications.push_error(&error);
+                return;
+            }
+        };
+        let style = *self.drawing_state.styles.get(DrawingTool::StrokeRect);
+        let single_image = images.len() == 1;
+        let mut matched = false;
+        for image in images {
+            let index = image
+                .file_name
+                .as_deref()
+                .and_then(|name| self.documents.iter().position(|d| d.name() == name))
+                // 1枚分だけのファイルは名前が違っても表示中の画像に追加する
+                .or_else(|| single_image.then_some(self.active));
+            let Some(document) = index.and_then(|index| self.documents.get_mut(index)) else {
+                continue;
+            };
+            matched = true;
+            if image.boxes.is_empty() {
+                continue;
+            }
+            document.add_items(image.boxes.iter().map(|b| b.to_item(&style)).collect());
+        }
+        if !matched {
+            let error = AppError::AnnotationImport {
+                reason: "ファイル名が一致する画像が開かれていません".to_string(),
+            };
+            self.ui_state.notifications.push_error(&error);
+        }
+    }
+
+    /// 前回のセッションが見つかった場合に復元するか確認する
+    fn handle_session_restore(&mut self, ctx: &egui::Context) {
+        if !self.session_loaded {
+            match AutoSave::take_load_result() {
+                Some(session) => {
+                    self.session_loaded = true;
+                    self.restorable_session = session;
+                }
+                None => ctx.request_repaint_after(std::time::Duration::from_millis(100)),
+            }
+        }
+
+        let answer = self
+            .restorable_session
+            .as_ref()
+            .and_then(|session| ui::show_restore_dialog(ctx, session));
+        match answer {
+            Some(true) => {
+                if let Some(session) = self.restorable_session.take() {
+                    self.restore_session(ctx, session);
+                }
+            }
+            Some(false) => self.restorable_session = None,
+            None => {}
+        }
+    }
+
+    fn restore_session(&mut self, ctx: &egui::Context, session: RestoredSession) {
+        let RestoredSession { snapshot, images } = session;
+        let first = self.documents.len();
+        for (saved, bytes) in snapshot.documents.into_iter().zip(images) {
+            let id = self.next_document_id;
+            let mut document = match Document::load(ctx, id, saved.source, bytes) {
+                Ok(document) => document,
+                Err(e) => {
+                    self.ui_state.notifications.push_error(&e);
+                    continue;
+                }
+            };
+            self.next_document_id += 1;
+            document.rectangles = saved.rectangles;
+            document.dirty = saved.dirty;
+            // 画像は保存済みなので書き込み直さない
+            document.image_key = saved.image_key;
+            document.viewport.zoom = saved.zoom;
+            document.viewport.pan = saved.pan;
+            document.view_command = None;
+            self.documents.push(document);
+        }
+        self.drawing_state.current_tool = snapshot.current_tool;
+        if self.documents.len() > first {
+            self.activate_document((first + snapshot.active).min(self.documents.len() - 1));
+        }
+    }
+
+    fn session_snapshot(&self) -> SessionSnapshot {
+        let documents = self
+            .documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| {
+                // 表示中のドキュメントの表示位置は UiState 側が最新
+                let viewport = if index == self.active {
+                    self.ui_state.viewport
+                } else {
+                    document.viewport
+                };
+                DocumentSnapshot {
+                    source: document.source.clone(),
+                    rectangles: document.rectangles.clone(),
+                    zoom: viewport.zoom,
+                    pan: viewport.pan,
+                    dirty: document.dirty,
+                    image_key: document.image_key.clone(),
+                }
+            })
+            .collect();
+        SessionSnapshot {
+            active: self.active,
+            current_tool: self.drawing_state.current_tool,
+            documents,
+        }
+    }
+
+    /// 自動保存の要否を判定するための値
+    fn session_revision(&self) -> SessionRevision {
+        SessionRevision {
+            documents: self
+                .documents
+                .iter()
+                .map(|document| (document.id, document.revision, document.dirty))
+                .collect(),
+            active: self.active,
+            current_tool: self.drawing_state.current_tool,
+            zoom: self.ui_state.viewport.zoom,
+            pan: self.ui_state.viewport.pan,
+        }
+    }
+
+    /// 変更が落ち着いたらセッションをブラウザのストレージに保存
+    fn autosave(&mut self, ctx: &egui::Context) {
+        // 前回のセッションの扱いが決まるまでは上書きしない
+        if !self.session_loaded || self.restorable_session.is_some() {
+            return;
+        }
+        for reason in AutoSave::take_errors() {
+            let error = AppError::AutoSave { reason };
+            self.ui_state.notifications.push_error(&error);
+        }
+        let now = ctx.input(|i| i.time);
+        if self.autosave_timer.update(self.session_revision(), now) {
+            // 画像データはドキュメントごとに初回のみ書き込む
+            let mut new_documents = Vec::new();
+            for (index, document) in self.documents.iter_mut().enumerate() {
+                if document.image_key.is_none() {
+                    document.image_key = Some(AutoSave::image_key(document.id));
+                    new_documents.push(index);
+                }
             }
+            let Ok(snapshot) = serde_json::to_string(&self.session_snapshot()) else {
+                return;
+            };
+            let new_images: Vec<(&str, &[u8])> = new_documents
+                .iter()
+                .filter_map(|&index| self.documents.get(index))
+                .filter_map(|document| {
+                    let key = document.image_key.as_deref()?;
+                    Some((key, document.image_bytes.as_slice()))
+                })
+                .collect();
+            let image_keys = self
+                .documents
+                .iter()
+                .filter_map(|document| document.image_key.clone())
+                .collect();
+            AutoSave::save(&snapshot, &new_images, image_keys);
+            self.autosave_timer.mark_saved();
+        } else if self.autosave_timer.is_pending() {
+            ctx.request_repaint_after(std::time::Duration::from_secs_f64(AUTOSAVE_DELAY));
         }
     }
 
@@ -287,23 +881,26 @@ impl AnnotoApp {
         &mut self,
         ui: &mut egui::Ui,
         image_response: &egui::Response,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
     ) {
         let pointer_pos = ui.input(|i| i.pointer.hover_pos());
+        let image_rect = viewport.image_screen_rect();
 
         // マルチタッチ中は描画処理をスキップ
         if self.ui_state.touch_points.len() >= 2 {
             return;
         }
 
+        let Some(document) = self.documents.get_mut(self.active) else {
+            return;
+        };
         if let Some(pos) = pointer_pos {
             if image_rect.contains(pos) {
-                let hovering_index = self
+                let hovering_index = document
                     .rectangles
                     .iter()
                     .enumerate()
-                    .find(|(_, item)| item.hit_test(pos, image_rect, scale))
+                    .find(|(_, item)| item.hit_test(pos, viewport))
                     .map(|(i, _)| i);
                 if hovering_index.is_some() {
                     return;
@@ -319,11 +916,18 @@ impl AnnotoApp {
                             self.drawing_state.current_tool,
                             start,
                             end,
-                            image_rect,
-                            scale,
+                            viewport,
                             &self.drawing_state,
+                            self.drawing_state
+                                .magnetic_snap
+                                .then(|| document.edge_map()),
                         ) {
-                            self.rectangles.push(shape);
+                            if let Some(color) = shape.get_stroke_color().or(shape.get_fill_color())
+                            {
+                                self.drawing_state.remember_color(color);
+                            }
+                            document.rectangles.push(shape);
+                            document.mark_changed();
                         }
                         self.drawing_state.drag_start = None;
                     }
@@ -332,78 +936,290 @@ impl AnnotoApp {
         }
     }
 
-    fn export_image(&self) {
-        web_sys::console::log_1(&"Exporting image".into());
-        if let Some(image_bytes) = &self.image_bytes {
-            match ImageExporter::export_image_with_resize(
-                image_bytes,
-                &self.rectangles,
-                &self.ui_state.export_format,
-                self.ui_state.export_resize_mode,
-                self.ui_state.export_resize_percentage,
-                self.ui_state.export_resize_pixels,
-            ) {
-                Ok(data) => {
-                    DownloadHandler::download_image(
-                        &data,
-                        &self.ui_state.export_format.to_lowercase(),
-                    );
-                }
-                Err(e) => {
-                    web_sys::console::log_1(&format!("Export error: {}", e).into());
-                }
-            }
+    fn export_image(&mut self) {
+        if let Some(document) = self.documents.get(self.active) {
+            let filename = FileNameTemplate::for_document(
+                document,
+                &self.ui_state,
+                &FileNameTemplate::today(),
+            );
+            self.export_task = Some(ExportTask::Image {
+                job: ImageExportJob::for_document(document, &self.ui_state),
+                document_id: document.id,
+                filename,
+                format: ImageExporter::output_format(&self.ui_state).to_string(),
+            });
         } else {
-            web_sys::console::log_1(&"No image bytes".into());
+            self.ui_state.notifications.push_error(&AppError::NoImage);
         }
     }
 
-    fn sync_ui_from_selection(&mut self) {
-        if let Some(idx) = self.selection_state.selected_item {
-            if let Some(item) = self.rectangles.get(idx) {
-                if let Some(w) = item.get_stroke_width() {
-                    self.drawing_state.stroke_width = w;
-                }
-                if let Some(c) = item.get_stroke_color() {
-                    self.drawing_state.stroke_color = c;
-                }
-                if let Some(c) = item.get_fill_color() {
-                    self.drawing_state.fill_color = c;
-                }
-                if let Some(r) = item.get_rounding() {
-                    self.drawing_state.rounding = r;
-                }
-                if let CanvasItem::Mosaic(mosaic) = item {
-                    self.drawing_state.mosaic_granularity = mosaic.get_granularity();
-                }
-            }
+    fn export_coco(&mut self) {
+        let result = DatasetFormat::to_coco(&self.documents)
+            .map_err(|reason| AppError::Encode {
+                name: COCO_FILENAME.to_string(),
+                reason,
+            })
+            .and_then(|json| DownloadHandler::download_json(&json, COCO_FILENAME));
+        if let Err(e) = result {
+            self.ui_state.notifications.push_error(&e);
+        }
+    }
+
+    fn export_pascal_voc(&mut self) {
+        let Some(document) = self.documents.get(self.active) else {
+            self.ui_state.notifications.push_error(&AppError::NoImage);
+            return;
+        };
+        let filename = DatasetFormat::pascal_voc_filename(document.name());
+        let result = DatasetFormat::to_pascal_voc(document)
+            .map_err(|reason| AppError::Encode {
+                name: filename.clone(),
+                reason,
+            })
+            .and_then(|xml| DownloadHandler::download_xml(&xml, &filename));
+        if let Err(e) = result {
+            self.ui_state.notifications.push_error(&e);
         }
     }
 
-    fn update_selected_item(&mut self) {
-        if let Some(idx) = self.selection_state.selected_item {
-            if let Some(item) = self.rectangles.get_mut(idx) {
-                item.set_stroke_width(self.drawing_state.stroke_width);
-                item.set_stroke_color(self.drawing_state.stroke_color);
-                item.set_fill_color(self.drawing_state.fill_color);
-                item.set_rounding(self.drawing_state.rounding);
+    fn export_all_images(&mut self) {
+        self.export_task = Some(ExportTask::Zip {
+            job: BatchExportJob::new(&self.documents, &self.ui_state),
+            document_ids: self.documents.iter().map(|d| d.id).collect(),
+        });
+    }
+
+    /// 実行中のエクスポートを進め、完了したらダウンロードする
+    fn step_export(&mut self, ctx: &egui::Context) {
+        let Some(task) = self.export_task.as_mut() else {
+            return;
+        };
+        let (result, notices) = match task {
+            ExportTask::Image { job, filename, .. } => {
+                let result = job.step().map(|result| {
+                    result.map_err(|reason| AppError::Encode {
+                        name: filename.clone(),
+                        reason,
+                    })
+                });
+                let notices = job
+                    .take_fallback_reason()
+                    .map(|reason| AppError::EncodeFallback {
+                        name: filename.clone(),
+                        reason,
+                    });
+                (result, notices.into_iter().collect())
             }
+            ExportTask::Zip { job, .. } => (job.step(), job.take_notices()),
+        };
+        for notice in &notices {
+            self.ui_state.notifications.push_error(notice);
+        }
+        let Some(result) = result else {
+            // 次のフレームで続きを処理する
+            ctx.request_repaint();
+            return;
+        };
+        let Some(task) = self.export_task.take() else {
+            return;
+        };
+        let downloaded = match task {
+            ExportTask::Image {
+                document_id,
+                filename,
+                format,
+                ..
+            } => result
+                .and_then(|data| DownloadHandler::download_image(&data, &format, &filename))
+                .map(|_| vec![document_id]),
+            ExportTask::Zip { document_ids, .. } => result
+                .and_then(|data| DownloadHandler::download_zip(&data, BATCH_ZIP_FILENAME))
+                .map(|_| document_ids),
+        };
+        let exported_ids = match downloaded {
+            Ok(ids) => ids,
+            Err(e) => {
+                self.ui_state.notifications.push_error(&e);
+                return;
+            }
+        };
+        for document in &mut self.documents {
+            if exported_ids.contains(&document.id) {
+                document.dirty = false;
+            }
+        }
+        self.ui_state.show_export_dialog = false;
+    }
+
+    /// サイドパネルでの変更を選択中のアイテムだけに適用
+    fn apply_style_changes(
+        &mut self,
+        changes: Vec<StyleChange>,
+        property_changes: Vec<PropertyChange>,
+    ) {
+        let Some(document) = self.documents.get_mut(self.active) else {
+            return;
+        };
+        let Some(item) = document
+            .selection_state
+            .selected_item()
+            .and_then(|idx| document.rectangles.get_mut(idx))
+        else {
+            return;
+        };
+        if changes.is_empty() && property_changes.is_empty() {
+            return;
+        }
+        for change in changes {
+            item.apply_style_change(change);
+        }
+        for change in property_changes {
+            item.apply_property_change(change);
         }
+        document.mark_changed();
     }
 
     fn handle_keyboard_events(&mut self, ctx: &egui::Context) {
-        ctx.input(|i| {
-            if i.key_pressed(egui::Key::Delete) || i.key_pressed(egui::Key::Backspace) {
-                if let Some(idx) = self.selection_state.selected_item {
-                    self.rectangles.remove(idx);
-                    self.selection_state.selected_item = None;
-                    self.selection_state.selected_handle = None;
+        // テキスト入力中やキー割り当ての変更中はショートカットを無視
+        if ctx.wants_keyboard_input() || self.ui_state.recording_shortcut.is_some() {
+            return;
+        }
+
+        // コピー・切り取り・貼り付けはブラウザのクリップボードイベントとして届く
+        let clipboard_events: Vec<egui::Event> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter(|e| {
+                    matches!(
+                        e,
+                        egui::Event::Copy | egui::Event::Cut | egui::Event::Paste(_)
+                    )
+                })
+                .cloned()
+                .collect()
+        });
+        for event in clipboard_events {
+            match event {
+                egui::Event::Copy => self.copy_selected_items(ctx),
+                egui::Event::Cut => {
+                    self.copy_selected_items(ctx);
+                    self.delete_selected_items();
                 }
+                egui::Event::Paste(text) => self.paste_items(Some(&text)),
+                _ => {}
             }
-        });
+        }
+
+        let (actions, shift) =
+            ctx.input_mut(|i| (self.keymap.consume_actions(i), i.modifiers.shift));
+        for action in actions {
+            self.run_shortcut(action, shift);
+        }
+    }
+
+    fn run_shortcut(&mut self, action: ShortcutAction, shift: bool) {
+        let nudge = if shift { 10.0 } else { 1.0 };
+        match action {
+            ShortcutAction::ToolStrokeRect => {
+                self.drawing_state.current_tool = DrawingTool::StrokeRect
+            }
+            ShortcutAction::ToolFilledRect => {
+                self.drawing_state.current_tool = DrawingTool::FilledRect
+            }
+            ShortcutAction::ToolArrow => self.drawing_state.current_tool = DrawingTool::Arrow,
+            ShortcutAction::ToolLine => self.drawing_state.current_tool = DrawingTool::Line,
+            ShortcutAction::ToolMosaic => self.drawing_state.current_tool = DrawingTool::Mosaic,
+            ShortcutAction::NudgeLeft => self.translate_selected_items(egui::vec2(-nudge, 0.0)),
+            ShortcutAction::NudgeRight => self.translate_selected_items(egui::vec2(nudge, 0.0)),
+            ShortcutAction::NudgeUp => self.translate_selected_items(egui::vec2(0.0, -nudge)),
+            ShortcutAction::NudgeDown => self.translate_selected_items(egui::vec2(0.0, nudge)),
+            ShortcutAction::Duplicate => self.duplicate_selected_items(),
+            ShortcutAction::SelectAll => {
+                if let Some(document) = self.active_document_mut() {
+                    let count = document.rectangles.len();
+                    document.selection_state.select_all(count);
+                }
+            }
+            ShortcutAction::Delete => self.delete_selected_items(),
+            ShortcutAction::Cancel => {
+                // スポイト使用中・ドラッグ中ならそれを取り消し、そうでなければ選択を解除
+                if self.ui_state.eyedropper.is_some() {
+                    self.ui_state.eyedropper = None;
+                } else if self.drawing_state.drag_start.is_some() {
+                    self.drawing_state.drag_start = None;
+                } else if let Some(document) = self.active_document_mut() {
+                    document.selection_state.clear();
+                }
+            }
+            ShortcutAction::ZoomIn => {
+                let viewport = &mut self.ui_state.viewport;
+                viewport.zoom_at_center(viewport.zoom * 1.25);
+            }
+            ShortcutAction::ZoomOut => {
+                let viewport = &mut self.ui_state.viewport;
+                viewport.zoom_at_center(viewport.zoom / 1.25);
+            }
+            ShortcutAction::ZoomReset => self.ui_state.viewport.zoom_at_center(100.0),
+            ShortcutAction::ShowHelp => {
+                self.ui_state.show_shortcut_help = !self.ui_state.show_shortcut_help
+            }
+        }
+    }
+
+    fn translate_selected_items(&mut self, delta: egui::Vec2) {
+        if let Some(document) = self.active_document_mut() {
+            document.translate_selected_items(delta);
+        }
+    }
+
+    fn duplicate_selected_items(&mut self) {
+        let Some(document) = self.active_document_mut() else {
+            return;
+        };
+        let mut duplicates = document.selected_items();
+        if duplicates.is_empty() {
+            return;
+        }
+        for item in &mut duplicates {
+            item.translate(egui::vec2(10.0, 10.0));
+        }
+        document.add_items(duplicates);
     }
 
-    fn handle_touch_events(&mut self, image_rect: egui::Rect, scale: f32) {
+    fn copy_selected_items(&mut self, ctx: &egui::Context) {
+        let items = self
+            .active_document()
+            .map(|document| document.selected_items())
+            .unwrap_or_default();
+        if items.is_empty() {
+            return;
+        }
+        let text = self.clipboard.copy(items);
+        ctx.copy_text(text);
+    }
+
+    /// 貼り付け先は表示中のドキュメント（別の画像からコピーしたものも貼り付け可能）
+    fn paste_items(&mut self, text: Option<&str>) {
+        if self.documents.is_empty() {
+            return;
+        }
+        let items = self.clipboard.paste(text);
+        if items.is_empty() {
+            return;
+        }
+        if let Some(document) = self.active_document_mut() {
+            document.add_items(items);
+        }
+    }
+
+    fn delete_selected_items(&mut self) {
+        if let Some(document) = self.active_document_mut() {
+            document.delete_selected_items();
+        }
+    }
+
+    fn handle_touch_events(&mut self, viewport: &Viewport) {
         // タッチポイントを更新（touch_handler から取得）
         self.ui_state.prev_touch_points = self.ui_state.touch_points.clone();
         self.ui_state.touch_points = get_current_touches();
@@ -424,7 +1240,7 @@ impl AnnotoApp {
                 // 距離の変化が大きい場合はピンチズーム
                 let distance_change = (curr_distance - prev_distance).abs();
                 if distance_change > 3.0 {
-                    self.handle_pinch_zoom(image_rect, scale);
+                    self.handle_pinch_zoom(viewport);
                 } else {
                     // 距離の変化が小さい場合はパンニング
                     self.handle_two_finger_pan();
@@ -456,10 +1272,10 @@ impl AnnotoApp {
         let pan_delta = curr_center - prev_center;
 
         // パンオフセットを更新
-        self.ui_state.pan_offset += pan_delta;
+        self.ui_state.viewport.pan_by(pan_delta);
     }
 
-    fn handle_pinch_zoom(&mut self, image_rect: egui::Rect, scale: f32) {
+    fn handle_pinch_zoom(&mut self, viewport: &Viewport) {
         let curr_points = &self.ui_state.touch_points;
         let prev_points = &self.ui_state.prev_touch_points;
 
@@ -488,25 +1304,44 @@ impl AnnotoApp {
         // ズーム比率を計算
         let zoom_ratio = curr_distance / prev_distance;
 
-        // 新しいズーム値を計算
-        let new_zoom = (self.drawing_state.zoom * zoom_ratio).clamp(1.0, 500.0);
-
-        // ピンチ中心がスクリーン上のどこにあるかを計算
-        // 画像座標系でのピンチ中心
-        if image_rect.contains(curr_center) {
-            // ピンチ中心のスクリーン座標から画像座標への変換
-            let pinch_screen_offset = curr_center - image_rect.min;
-            let pinch_image_pos = pinch_screen_offset / scale;
+        // ピンチ中心を固定したまま拡大縮小
+        if viewport.image_screen_rect().contains(curr_center) {
+            let viewport = &mut self.ui_state.viewport;
+            viewport.zoom_at(curr_center, viewport.zoom * zoom_ratio);
+        }
+    }
 
-            // ズーム前後での画像座標の変化を計算
-            let zoom_change = new_zoom / self.drawing_state.zoom;
+    /// Ctrl+ホイールでカーソル位置を中心に拡大縮小、ホイールのみでスクロール
+    fn handle_wheel(&mut self, ui: &egui::Ui, canvas_response: &egui::Response) {
+        // ダイアログなどのウィンドウ上でのホイールはキャンバスに渡さない
+        if !canvas_response.contains_pointer() {
+            return;
+        }
+        let (zoom_delta, scroll_delta, pointer_pos) =
+            ui.input(|i| (i.zoom_delta(), i.smooth_scroll_delta, i.pointer.hover_pos()));
+        let Some(pos) = pointer_pos else {
+            return;
+        };
 
-            // パンオフセットを調整して、ピンチ中心が固定されるようにする
-            // ピンチ中心が画面上の同じ位置に留まるようにオフセットを調整
-            let offset_adjustment = pinch_image_pos * (1.0 - zoom_change) * scale;
-            self.ui_state.pan_offset += offset_adjustment;
+        let viewport = &mut self.ui_state.viewport;
+        viewport.pan_by(scroll_delta);
+        // ピンチはタッチイベント側で処理するため二重に適用しない
+        if zoom_delta != 1.0 && self.ui_state.touch_points.len() < 2 {
+            viewport.zoom_at(pos, viewport.zoom * zoom_delta);
+        }
+    }
 
-            self.drawing_state.zoom = new_zoom;
+    /// 中ボタンドラッグまたはスペース＋ドラッグで表示位置を移動（キャンバス上で押した場合のみ）
+    fn handle_view_panning(&mut self, ui: &egui::Ui, canvas_response: &egui::Response) -> bool {
+        let (pan_buttons, delta) = ui.input(|i| {
+            let space_drag = i.key_down(egui::Key::Space) && i.pointer.primary_down();
+            (i.pointer.middle_down() || space_drag, i.pointer.delta())
+        });
+        // ボタンを押したままキャンバスの外に出ても続ける
+        let panning = pan_buttons && canvas_response.is_pointer_button_down_on();
+        if panning {
+            self.ui_state.viewport.pan_by(delta);
         }
+        panning
     }
 }
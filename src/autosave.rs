@@ -0,0 +1,417 @@
+use crate::canvas_items::CanvasItem;
+use crate::document::SourceFile;
+use crate::drawing_tool::DrawingTool;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use wasm_bindgen::prelude::*;
+
+const DB_NAME: &str = "annoto";
+const STORE_NAME: &str = "session";
+const SESSION_KEY: &str = "autosave";
+/// ドキュメントの画像データを保存するキーの接頭辞
+const IMAGE_KEY_PREFIX: &str = "image_";
+
+/// 最後の変更からこの秒数だけ操作がなければ保存する
+pub const AUTOSAVE_DELAY: f64 = 2.0;
+
+/// 保存するセッションの内容（画像データ以外）
+#[derive(Serialize, Deserialize)]
+pub struct SessionSnapshot {
+    pub active: usize,
+    pub current_tool: DrawingTool,
+    pub documents: Vec<DocumentSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct DocumentSnapshot {
+    pub source: SourceFile,
+    pub rectangles: Vec<CanvasItem>,
+    pub zoom: f32,
+    pub pan: egui::Vec2,
+    pub dirty: bool,
+    /// 画像データを保存したキー
+    pub image_key: Option<String>,
+}
+
+/// 保存する内容が変わったかの判定に使う値（アイテムの中身の代わりに変更回数を比べる）
+#[derive(Clone, PartialEq)]
+pub struct SessionRevision {
+    /// ドキュメントごとの ID・変更回数・未保存の変更があるか
+    pub documents: Vec<(u64, u64, bool)>,
+    pub active: usize,
+    pub current_tool: DrawingTool,
+    pub zoom: f32,
+    pub pan: egui::Vec2,
+}
+
+/// ストレージから読み込んだ前回のセッション
+pub struct RestoredSession {
+    pub snapshot: SessionSnapshot,
+    pub images: Vec<Vec<u8>>,
+}
+
+struct LoadState {
+    finished: bool,
+    session: Option<RestoredSession>,
+}
+
+/// 画像の読み込み待ちのセッション
+struct PendingImages {
+    snapshot: Option<SessionSnapshot>,
+    images: Vec<Option<Vec<u8>>>,
+    remaining: usize,
+}
+
+static LOAD_STATE: Lazy<Arc<Mutex<LoadState>>> = Lazy::new(|| {
+    Arc::new(Mutex::new(LoadState {
+        finished: false,
+        session: None,
+    }))
+});
+
+/// 保存に失敗した理由（画面に通知するまで溜めておく）
+static SAVE_ERRORS: Lazy<Arc<Mutex<Vec<String>>>> = Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
+
+thread_local! {
+    /// 開いたデータベース（保存のたびに接続を増やさないよう使い回す）。
+    /// JavaScript のオブジェクトはスレッド間で共有できないため thread_local に置く
+    static DATABASE: RefCell<Option<web_sys::IdbDatabase>> = const { RefCell::new(None) };
+}
+
+/// IndexedDB へのセッションの保存と読み込み
+pub struct AutoSave;
+
+impl AutoSave {
+    /// 前回のセッションの読み込みを開始（結果は take_load_result で受け取る）
+    pub fn load() {
+        Self::open(
+            |db| {
+                let store = db
+                    .transaction_with_str(STORE_NAME)
+                    .and_then(|tx| tx.object_store(STORE_NAME));
+                let request = store
+                    .as_ref()
+                    .ok()
+                    .and_then(|store| store.get(&JsValue::from_str(SESSION_KEY)).ok());
+                let (Ok(store), Some(request)) = (store, request) else {
+                    Self::finish_load(None);
+                    return;
+                };
+                let result_request = request.clone();
+                request.set_onsuccess(Some(
+                    Closure::once_into_js(move |_: web_sys::Event| {
+                        let snapshot = result_request
+                            .result()
+                            .ok()
+                            .and_then(|value| value.as_string())
+                            .and_then(|json| serde_json::from_str::<SessionSnapshot>(&json).ok());
+                        match snapshot {
+                            Some(snapshot) => Self::load_images(&store, snapshot),
+                            None => Self::finish_load(None),
+                        }
+                    })
+                    .unchecked_ref(),
+                ));
+                request.set_onerror(Some(
+                    Closure::once_into_js(move |_: web_sys::Event| Self::finish_load(None))
+                        .unchecked_ref(),
+                ));
+            },
+            || Self::finish_load(None),
+        );
+    }
+
+    /// 読み込みが完了していれば結果を返す（完了後の最初の1回のみ）
+    pub fn take_load_result() -> Option<Option<RestoredSession>> {
+        let mut state = LOAD_STATE.lock().unwrap();
+        if !state.finished {
+            return None;
+        }
+        state.finished = false;
+        Some(state.session.take())
+    }
+
+    /// 新しく保存するドキュメントの画像データのキー
+    pub fn image_key(document_id: u64) -> String {
+        // 前回のセッションの画像と重ならないよう時刻を含める
+        format!(
+            "{}{}_{}",
+            IMAGE_KEY_PREFIX,
+            js_sys::Date::now() as u64,
+            document_id
+        )
+    }
+
+    /// セッションの JSON と、まだ保存していない画像を保存する。
+    /// どのドキュメントからも参照されなくなった画像は削除する
+    pub fn save(snapshot: &str, new_images: &[(&str, &[u8])], image_keys: Vec<String>) {
+        let snapshot = JsValue::from_str(snapshot);
+        let new_images: Vec<(JsValue, JsValue)> = new_images
+            .iter()
+            .map(|(key, bytes)| {
+                (
+                    JsValue::from_str(key),
+                    js_sys::Uint8Array::from(*bytes).into(),
+                )
+            })
+            .collect();
+        Self::open(
+            move |db| {
+                let result = Self::write(&db, |store| {
+                    for (key, bytes) in &new_images {
+                        store.put_with_key(bytes, key)?;
+                    }
+                    store.put_with_key(&snapshot, &JsValue::from_str(SESSION_KEY))?;
+                    Self::delete_unused_images(store, image_keys)
+                });
+                if let Err(e) = result {
+                    Self::report_error(format!("{:?}", e));
+                }
+            },
+            || Self::report_error("IndexedDB を利用できません".to_string()),
+        );
+    }
+
+    /// 保存に失敗した理由を取り出す
+    pub fn take_errors() -> Vec<String> {
+        std::mem::take(&mut *SAVE_ERRORS.lock().unwrap())
+    }
+
+    /// 書き込み用のトランザクションで処理を行い、非同期に発生したエラーも通知する
+    fn write(
+        db: &web_sys::IdbDatabase,
+        f: impl FnOnce(&web_sys::IdbObjectStore) -> Result<(), JsValue>,
+    ) -> Result<(), JsValue> {
+        let tx =
+            db.transaction_with_str_and_mode(STORE_NAME, web_sys::IdbTransactionMode::Readwrite)?;
+        // 容量不足などはリクエストの完了後にトランザクションの中断として届く
+        let error_tx = tx.clone();
+        tx.set_onabort(Some(
+            Closure::once_into_js(move |_: web_sys::Event| {
+                let reason = match error_tx.error() {
+                    Some(e) if e.name() == "QuotaExceededError" => {
+                        "ブラウザの保存容量が不足しています".to_string()
+                    }
+                    Some(e) => format!("{}: {}", e.name(), e.message()),
+                    None => "保存が中断されました".to_string(),
+                };
+                Self::report_error(reason);
+            })
+            .unchecked_ref(),
+        ));
+        f(&tx.object_store(STORE_NAME)?)
+    }
+
+    fn delete_unused_images(
+        store: &web_sys::IdbObjectStore,
+        image_keys: Vec<String>,
+    ) -> Result<(), JsValue> {
+        let request = store.get_all_keys()?;
+        let result_request = request.clone();
+        let store = store.clone();
+        request.set_onsuccess(Some(
+            Closure::once_into_js(move |_: web_sys::Event| {
+                let Some(keys) = result_request
+                    .result()
+                    .ok()
+                    .and_then(|keys| keys.dyn_into::<js_sys::Array>().ok())
+                else {
+                    return;
+                };
+                for key in keys.iter() {
+                    let unused = key.as_string().is_some_and(|key| {
+                        key.starts_with(IMAGE_KEY_PREFIX) && !image_keys.contains(&key)
+                    });
+                    if unused {
+                        let _ = store.delete(&key);
+                    }
+                }
+            })
+            .unchecked_ref(),
+        ));
+        Ok(())
+    }
+
+    fn report_error(reason: String) {
+        web_sys::console::log_1(&format!("Autosave error: {}", reason).into());
+        let mut errors = SAVE_ERRORS.lock().unwrap();
+        // 同じ理由で何度も失敗した場合は1回だけ通知する
+        if !errors.contains(&reason) {
+            errors.push(reason);
+        }
+    }
+
+    /// データベースを開く（開いたものがあればそれを使う）
+    fn open(
+        on_open: impl FnOnce(web_sys::IdbDatabase) + 'static,
+        on_error: impl FnOnce() + 'static,
+    ) {
+        if let Some(db) = DATABASE.with_borrow(Option::clone) {
+            on_open(db);
+            return;
+        }
+        let request = web_sys::window()
+            .and_then(|window| window.indexed_db().ok().flatten())
+            .and_then(|factory| factory.open_with_u32(DB_NAME, 1).ok());
+        let Some(request) = request else {
+            on_error();
+            return;
+        };
+
+        request.set_onupgradeneeded(Some(
+            Closure::once_into_js(move |event: web_sys::Event| {
+                let db = Self::database_from_event(&event)
+                    .filter(|db| !db.object_store_names().contains(STORE_NAME));
+                if let Some(db) = db {
+                    let _ = db.create_object_store(STORE_NAME);
+                }
+            })
+            .unchecked_ref(),
+        ));
+        request.set_onsuccess(Some(
+            Closure::once_into_js(move |event: web_sys::Event| {
+                if let Some(db) = Self::database_from_event(&event) {
+                    on_open(Self::cache_database(db));
+                }
+            })
+            .unchecked_ref(),
+        ));
+        request.set_onerror(Some(
+            Closure::once_into_js(move |_: web_sys::Event| on_error()).unchecked_ref(),
+        ));
+    }
+
+    /// 開いたデータベースを記録する。同時に開いた接続が既にあればそちらを使い、新しい接続は閉じる
+    fn cache_database(db: web_sys::IdbDatabase) -> web_sys::IdbDatabase {
+        DATABASE.with_borrow_mut(|cached| {
+            if let Some(cached) = cached {
+                db.close();
+                return cached.clone();
+            }
+            // 別のタブでバージョンが上がる場合やブラウザに閉じられた場合は、次回に開き直す
+            let closing = db.clone();
+            db.set_onversionchange(Some(
+                Closure::once_into_js(move |_: web_sys::Event| {
+                    closing.close();
+                    DATABASE.with_borrow_mut(|cached| *cached = None);
+                })
+                .unchecked_ref(),
+            ));
+            db.set_onclose(Some(
+                Closure::once_into_js(move |_: web_sys::Event| {
+                    DATABASE.with_borrow_mut(|cached| *cached = None);
+                })
+                .unchecked_ref(),
+            ));
+            *cached = Some(db.clone());
+            db
+        })
+    }
+
+    fn database_from_event(event: &web_sys::Event) -> Option<web_sys::IdbDatabase> {
+        event
+            .target()
+            .and_then(|target| target.dyn_into::<web_sys::IdbOpenDbRequest>().ok())
+            .and_then(|request| request.result().ok())
+            .and_then(|result| result.dyn_into::<web_sys::IdbDatabase>().ok())
+    }
+
+    /// ドキュメントごとに保存した画像を読み込む
+    fn load_images(store: &web_sys::IdbObjectStore, snapshot: SessionSnapshot) {
+        let keys: Option<Vec<String>> = snapshot
+            .documents
+            .iter()
+            .map(|document| document.image_key.clone())
+            .collect();
+        let Some(keys) = keys.filter(|keys| !keys.is_empty()) else {
+            Self::finish_load(None);
+            return;
+        };
+        let pending = Rc::new(RefCell::new(PendingImages {
+            snapshot: Some(snapshot),
+            images: vec![None; keys.len()],
+            remaining: keys.len(),
+        }));
+        for (index, key) in keys.iter().enumerate() {
+            let Ok(request) = store.get(&JsValue::from_str(key)) else {
+                Self::finish_load(None);
+                return;
+            };
+            let result_request = request.clone();
+            let on_success = pending.clone();
+            request.set_onsuccess(Some(
+                Closure::once_into_js(move |_: web_sys::Event| {
+                    let image = result_request
+                        .result()
+                        .ok()
+                        .filter(|value| value.is_instance_of::<js_sys::Uint8Array>())
+                        .map(|value| js_sys::Uint8Array::new(&value).to_vec());
+                    Self::image_loaded(&on_success, index, image);
+                })
+                .unchecked_ref(),
+            ));
+            let on_error = pending.clone();
+            request.set_onerror(Some(
+                Closure::once_into_js(move |_: web_sys::Event| {
+                    Self::image_loaded(&on_error, index, None)
+                })
+                .unchecked_ref(),
+            ));
+        }
+    }
+
+    fn image_loaded(pending: &RefCell<PendingImages>, index: usize, image: Option<Vec<u8>>) {
+        let mut pending = pending.borrow_mut();
+        pending.images[index] = image;
+        pending.remaining -= 1;
+        if pending.remaining > 0 {
+            return;
+        }
+        // 画像が1枚でも欠けているセッションは復元しない
+        let images: Option<Vec<Vec<u8>>> =
+            std::mem::take(&mut pending.images).into_iter().collect();
+        let session = pending
+            .snapshot
+            .take()
+            .zip(images)
+            .map(|(snapshot, images)| RestoredSession { snapshot, images });
+        Self::finish_load(session);
+    }
+
+    fn finish_load(session: Option<RestoredSession>) {
+        let mut state = LOAD_STATE.lock().unwrap();
+        state.finished = true;
+        state.session = session;
+    }
+}
+
+/// 内容の変化を監視し、変化が落ち着いてから保存するためのタイマー
+#[derive(Default)]
+pub struct AutoSaveTimer {
+    observed: Option<SessionRevision>,
+    changed_at: f64,
+    saved: Option<SessionRevision>,
+}
+
+impl AutoSaveTimer {
+    /// 現在の内容を記録し、保存すべきタイミングであれば true を返す
+    pub fn update(&mut self, revision: SessionRevision, now: f64) -> bool {
+        if self.observed.as_ref() != Some(&revision) {
+            self.observed = Some(revision);
+            self.changed_at = now;
+        }
+        self.is_pending() && now - self.changed_at >= AUTOSAVE_DELAY
+    }
+
+    /// 未保存の変更があるか
+    pub fn is_pending(&self) -> bool {
+        self.observed != self.saved
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = self.observed.clone();
+    }
+}
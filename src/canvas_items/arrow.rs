@@ -1,9 +1,11 @@
+use crate::viewport::Viewport;
 use egui::{
-    Pos2, Rect, Ui,
+    Pos2, Ui,
     epaint::{ColorMode, PathShape, PathStroke},
 };
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Arrow {
     pub start_x: f32,
     pub start_y: f32,
@@ -11,30 +13,26 @@ pub struct Arrow {
     pub end_y: f32,
 
     pub color: egui::Color32,
+    #[serde(default = "super::default_opacity")]
+    pub opacity: f32,
+    /// クラス名・メモ・タグ
+    #[serde(flatten)]
+    pub metadata: super::ItemMetadata,
 }
 
 impl Arrow {
-    pub fn render(&self, ui: &mut Ui, image_rect: Rect, scale: f32) {
-        let start = image_rect.min
-            + (egui::Pos2 {
-                x: self.start_x,
-                y: self.start_y,
-            } * scale)
-                .to_vec2();
-        let end = image_rect.min
-            + (egui::Pos2 {
-                x: self.end_x,
-                y: self.end_y,
-            } * scale)
-                .to_vec2();
+    pub fn render(&self, ui: &mut Ui, viewport: &Viewport) {
+        let start = viewport.image_to_screen(egui::pos2(self.start_x, self.start_y));
+        let end = viewport.image_to_screen(egui::pos2(self.end_x, self.end_y));
 
         let dx = end.x - start.x;
         let dy = end.y - start.y;
         let line_rad = dy.atan2(dx);
+        let color = super::with_opacity(self.color, self.opacity);
 
         let path_stroke = PathStroke {
             width: 1.0,
-            color: ColorMode::Solid(self.color),
+            color: ColorMode::Solid(color),
             kind: egui::StrokeKind::Inside,
         };
         let mut points = vec![];
@@ -42,15 +40,35 @@ impl Arrow {
             x: start.x,
             y: start.y,
         });
-        points.push(Self::calc_point(&end, line_rad, 20.0, 65.0 * scale));
-        points.push(Self::calc_point(&end, line_rad, 30.0, 75.0 * scale));
+        points.push(Self::calc_point(
+            &end,
+            line_rad,
+            20.0,
+            viewport.image_len_to_screen(65.0),
+        ));
+        points.push(Self::calc_point(
+            &end,
+            line_rad,
+            30.0,
+            viewport.image_len_to_screen(75.0),
+        ));
         points.push(Pos2 { x: end.x, y: end.y });
-        points.push(Self::calc_point(&end, line_rad, -30.0, 75.0 * scale));
-        points.push(Self::calc_point(&end, line_rad, -20.0, 65.0 * scale));
+        points.push(Self::calc_point(
+            &end,
+            line_rad,
+            -30.0,
+            viewport.image_len_to_screen(75.0),
+        ));
+        points.push(Self::calc_point(
+            &end,
+            line_rad,
+            -20.0,
+            viewport.image_len_to_screen(65.0),
+        ));
         let path = PathShape {
             points: points,
             closed: true,
-            fill: self.color,
+            fill: color,
             stroke: path_stroke,
         };
         ui.painter().add(path);
@@ -70,19 +88,9 @@ impl Arrow {
         }
     }
 
-    pub fn hit_test(&self, pos: egui::Pos2, image_rect: egui::Rect, scale: f32) -> bool {
-        let start = image_rect.min
-            + (egui::Pos2 {
-                x: self.start_x,
-                y: self.start_y,
-            } * scale)
-                .to_vec2();
-        let end = image_rect.min
-            + (egui::Pos2 {
-                x: self.end_x,
-                y: self.end_y,
-            } * scale)
-                .to_vec2();
+    pub fn hit_test(&self, pos: egui::Pos2, viewport: &Viewport) -> bool {
+        let start = viewport.image_to_screen(egui::pos2(self.start_x, self.start_y));
+        let end = viewport.image_to_screen(egui::pos2(self.end_x, self.end_y));
         // Simple line distance check
         let dist = Self::point_to_line_distance(pos, start, end);
         dist < 15.0 // threshold
@@ -111,24 +119,11 @@ impl Arrow {
 
     pub fn get_handles(
         &self,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
     ) -> Vec<(egui::Pos2, crate::canvas_items::Handle)> {
         let mut handles = Vec::new();
-        let start_world = image_rect.min
-            + egui::Pos2 {
-                x: self.start_x,
-                y: self.start_y,
-            }
-            .to_vec2()
-                * scale;
-        let end_world = image_rect.min
-            + egui::Pos2 {
-                x: self.end_x,
-                y: self.end_y,
-            }
-            .to_vec2()
-                * scale;
+        let start_world = viewport.image_to_screen(egui::pos2(self.start_x, self.start_y));
+        let end_world = viewport.image_to_screen(egui::pos2(self.end_x, self.end_y));
         handles.push((start_world, crate::canvas_items::Handle::Start));
         handles.push((end_world, crate::canvas_items::Handle::End));
         handles
@@ -145,12 +140,7 @@ impl Arrow {
 
     pub fn draw_on_pixmap(&self, pixmap: &mut tiny_skia::Pixmap) {
         let mut paint = tiny_skia::Paint::default();
-        paint.set_color_rgba8(
-            self.color.r(),
-            self.color.g(),
-            self.color.b(),
-            self.color.a(),
-        );
+        paint.set_color(super::pixmap_color(self.color, self.opacity));
         paint.anti_alias = true;
 
         let dx = self.end_x - self.start_x;
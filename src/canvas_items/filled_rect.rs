@@ -1,4 +1,7 @@
-#[derive(Clone)]
+use crate::viewport::Viewport;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct FilledRect {
     pub x1: f32,
     pub y1: f32,
@@ -6,45 +9,30 @@ pub struct FilledRect {
     pub y2: f32,
 
     pub filled_color: egui::Color32,
+    #[serde(default = "super::default_opacity")]
+    pub opacity: f32,
     pub rounding: u8,
+    /// クラス名・メモ・タグ
+    #[serde(flatten)]
+    pub metadata: super::ItemMetadata,
 }
 
 impl FilledRect {
-    pub fn render(&self, ui: &mut egui::Ui, image_rect: egui::Rect, scale: f32) {
-        let world_min = image_rect.min
-            + (egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            } * scale)
-                .to_vec2();
-        let world_max = image_rect.min
-            + (egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            } * scale)
-                .to_vec2();
+    pub fn render(&self, ui: &mut egui::Ui, viewport: &Viewport) {
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         let world_rect = egui::Rect::from_min_max(world_min, world_max);
 
         ui.painter().rect_filled(
             world_rect,
             egui::CornerRadius::same(self.rounding),
-            self.filled_color,
+            super::with_opacity(self.filled_color, self.opacity),
         );
     }
 
-    pub fn hit_test(&self, pos: egui::Pos2, image_rect: egui::Rect, scale: f32) -> bool {
-        let world_min = image_rect.min
-            + (egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            } * scale)
-                .to_vec2();
-        let world_max = image_rect.min
-            + (egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            } * scale)
-                .to_vec2();
+    pub fn hit_test(&self, pos: egui::Pos2, viewport: &Viewport) -> bool {
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         let world_rect = egui::Rect::from_min_max(world_min, world_max);
         world_rect.contains(pos)
     }
@@ -89,24 +77,11 @@ impl FilledRect {
 
     pub fn get_handles(
         &self,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
     ) -> Vec<(egui::Pos2, crate::canvas_items::Handle)> {
         let mut handles = Vec::new();
-        let world_min = image_rect.min
-            + egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            }
-            .to_vec2()
-                * scale;
-        let world_max = image_rect.min
-            + egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            }
-            .to_vec2()
-                * scale;
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         handles.push((world_min, crate::canvas_items::Handle::Corner(0)));
         handles.push((
             egui::Pos2::new(world_max.x, world_min.y),
@@ -122,12 +97,7 @@ impl FilledRect {
 
     pub fn draw_on_pixmap(&self, pixmap: &mut tiny_skia::Pixmap) {
         let mut paint = tiny_skia::Paint::default();
-        paint.set_color_rgba8(
-            self.filled_color.r(),
-            self.filled_color.g(),
-            self.filled_color.b(),
-            self.filled_color.a(),
-        );
+        paint.set_color(super::pixmap_color(self.filled_color, self.opacity));
         paint.anti_alias = true;
 
         let rect =
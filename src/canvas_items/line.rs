@@ -1,4 +1,7 @@
-#[derive(Clone)]
+use crate::viewport::Viewport;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Line {
     pub start_x: f32,
     pub start_y: f32,
@@ -7,43 +10,31 @@ pub struct Line {
 
     pub stroke_width: f32,
     pub stroke_color: egui::Color32,
+    #[serde(default = "super::default_opacity")]
+    pub opacity: f32,
+    /// クラス名・メモ・タグ
+    #[serde(flatten)]
+    pub metadata: super::ItemMetadata,
 }
 
 impl Line {
-    pub fn render(&self, ui: &mut egui::Ui, image_rect: egui::Rect, scale: f32) {
-        let start = image_rect.min
-            + (egui::Pos2 {
-                x: self.start_x,
-                y: self.start_y,
-            } * scale)
-                .to_vec2();
-        let end = image_rect.min
-            + (egui::Pos2 {
-                x: self.end_x,
-                y: self.end_y,
-            } * scale)
-                .to_vec2();
+    pub fn render(&self, ui: &mut egui::Ui, viewport: &Viewport) {
+        let start = viewport.image_to_screen(egui::pos2(self.start_x, self.start_y));
+        let end = viewport.image_to_screen(egui::pos2(self.end_x, self.end_y));
 
-        let stroke_width = self.stroke_width * scale;
+        let stroke_width = viewport.image_len_to_screen(self.stroke_width);
         ui.painter().line_segment(
             [start, end],
-            egui::Stroke::new(stroke_width, self.stroke_color),
+            egui::Stroke::new(
+                stroke_width,
+                super::with_opacity(self.stroke_color, self.opacity),
+            ),
         );
     }
 
-    pub fn hit_test(&self, pos: egui::Pos2, image_rect: egui::Rect, scale: f32) -> bool {
-        let start = image_rect.min
-            + (egui::Pos2 {
-                x: self.start_x,
-                y: self.start_y,
-            } * scale)
-                .to_vec2();
-        let end = image_rect.min
-            + (egui::Pos2 {
-                x: self.end_x,
-                y: self.end_y,
-            } * scale)
-                .to_vec2();
+    pub fn hit_test(&self, pos: egui::Pos2, viewport: &Viewport) -> bool {
+        let start = viewport.image_to_screen(egui::pos2(self.start_x, self.start_y));
+        let end = viewport.image_to_screen(egui::pos2(self.end_x, self.end_y));
         let dist = Self::point_to_line_distance(pos, start, end);
         dist < 15.0 // threshold
     }
@@ -71,24 +62,11 @@ impl Line {
 
     pub fn get_handles(
         &self,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
     ) -> Vec<(egui::Pos2, crate::canvas_items::Handle)> {
         let mut handles = Vec::new();
-        let start_world = image_rect.min
-            + egui::Pos2 {
-                x: self.start_x,
-                y: self.start_y,
-            }
-            .to_vec2()
-                * scale;
-        let end_world = image_rect.min
-            + egui::Pos2 {
-                x: self.end_x,
-                y: self.end_y,
-            }
-            .to_vec2()
-                * scale;
+        let start_world = viewport.image_to_screen(egui::pos2(self.start_x, self.start_y));
+        let end_world = viewport.image_to_screen(egui::pos2(self.end_x, self.end_y));
         handles.push((start_world, crate::canvas_items::Handle::Start));
         handles.push((end_world, crate::canvas_items::Handle::End));
         handles
@@ -105,12 +83,7 @@ impl Line {
 
     pub fn draw_on_pixmap(&self, pixmap: &mut tiny_skia::Pixmap) {
         let mut paint = tiny_skia::Paint::default();
-        paint.set_color_rgba8(
-            self.stroke_color.r(),
-            self.stroke_color.g(),
-            self.stroke_color.b(),
-            self.stroke_color.a(),
-        );
+        paint.set_color(super::pixmap_color(self.stroke_color, self.opacity));
         paint.anti_alias = true;
 
         let mut path = tiny_skia::PathBuilder::new();
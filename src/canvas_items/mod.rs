@@ -10,6 +10,10 @@ pub use line::Line;
 pub use mosaic::Mosaic;
 pub use stroke_rect::StrokeRect;
 
+use crate::drawing_tool::DrawingTool;
+use crate::viewport::Viewport;
+use serde::{Deserialize, Serialize};
+
 #[derive(Clone, Debug)]
 pub enum Handle {
     Corner(usize),
@@ -17,7 +21,75 @@ pub enum Handle {
     End,
 }
 
-#[derive(Clone)]
+/// サイドパネルからアイテムに対して行うスタイルの変更
+#[derive(Clone, Copy, Debug)]
+pub enum StyleChange {
+    StrokeWidth(f32),
+    StrokeColor(egui::Color32),
+    FillColor(egui::Color32),
+    Rounding(u8),
+    MosaicGranularity(u8),
+    Opacity(f32),
+}
+
+/// 旧バージョンで保存したアイテムは不透明として読み込む
+pub fn default_opacity() -> f32 {
+    1.0
+}
+
+/// 画面描画用の色（egui の Color32 は乗算済みなので全チャンネルに不透明度を掛ける）
+pub fn with_opacity(color: egui::Color32, opacity: f32) -> egui::Color32 {
+    color.gamma_multiply(opacity.clamp(0.0, 1.0))
+}
+
+/// エクスポート用の色（tiny-skia の Paint には乗算前の値を渡す）
+pub fn pixmap_color(color: egui::Color32, opacity: f32) -> tiny_skia::Color {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    let alpha = (a as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+    tiny_skia::Color::from_rgba8(r, g, b, alpha)
+}
+
+/// アイテムに付ける説明（描画には影響しない）
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ItemMetadata {
+    /// クラス名（未設定なら空）
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    /// 自由記述のメモ
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub note: String,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+}
+
+impl ItemMetadata {
+    /// ホバー時に表示する内容（何も設定されていなければ None）
+    pub fn summary(&self) -> Option<String> {
+        let mut lines = Vec::new();
+        if !self.label.is_empty() {
+            lines.push(self.label.clone());
+        }
+        if !self.tags.is_empty() {
+            let tags: Vec<String> = self.tags.iter().map(|tag| format!("#{}", tag)).collect();
+            lines.push(tags.join(" "));
+        }
+        if !self.note.is_empty() {
+            lines.push(self.note.clone());
+        }
+        (!lines.is_empty()).then(|| lines.join("\n"))
+    }
+}
+
+/// サイドパネルからアイテムに対して行うスタイル以外の変更
+#[derive(Clone, Debug)]
+pub enum PropertyChange {
+    Label(String),
+    Note(String),
+    Tags(Vec<String>),
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub enum CanvasItem {
     StrokeRect(StrokeRect),
     FilledRect(FilledRect),
@@ -27,13 +99,24 @@ pub enum CanvasItem {
 }
 
 impl CanvasItem {
-    pub fn hit_test(&self, pos: egui::Pos2, image_rect: egui::Rect, scale: f32) -> bool {
+    /// このアイテムを描くツール
+    pub fn tool(&self) -> DrawingTool {
+        match self {
+            CanvasItem::StrokeRect(_) => DrawingTool::StrokeRect,
+            CanvasItem::FilledRect(_) => DrawingTool::FilledRect,
+            CanvasItem::Arrow(_) => DrawingTool::Arrow,
+            CanvasItem::Line(_) => DrawingTool::Line,
+            CanvasItem::Mosaic(_) => DrawingTool::Mosaic,
+        }
+    }
+
+    pub fn hit_test(&self, pos: egui::Pos2, viewport: &Viewport) -> bool {
         match self {
-            CanvasItem::StrokeRect(item) => item.hit_test(pos, image_rect, scale),
-            CanvasItem::FilledRect(item) => item.hit_test(pos, image_rect, scale),
-            CanvasItem::Arrow(item) => item.hit_test(pos, image_rect, scale),
-            CanvasItem::Line(item) => item.hit_test(pos, image_rect, scale),
-            CanvasItem::Mosaic(item) => item.hit_test(pos, image_rect, scale),
+            CanvasItem::StrokeRect(item) => item.hit_test(pos, viewport),
+            CanvasItem::FilledRect(item) => item.hit_test(pos, viewport),
+            CanvasItem::Arrow(item) => item.hit_test(pos, viewport),
+            CanvasItem::Line(item) => item.hit_test(pos, viewport),
+            CanvasItem::Mosaic(item) => item.hit_test(pos, viewport),
         }
     }
 
@@ -57,13 +140,73 @@ impl CanvasItem {
         }
     }
 
-    pub fn get_handles(&self, image_rect: egui::Rect, scale: f32) -> Vec<(egui::Pos2, Handle)> {
+    pub fn get_handles(&self, viewport: &Viewport) -> Vec<(egui::Pos2, Handle)> {
         match self {
-            CanvasItem::StrokeRect(item) => item.get_handles(image_rect, scale),
-            CanvasItem::FilledRect(item) => item.get_handles(image_rect, scale),
-            CanvasItem::Arrow(item) => item.get_handles(image_rect, scale),
-            CanvasItem::Line(item) => item.get_handles(image_rect, scale),
-            CanvasItem::Mosaic(item) => item.get_handles(image_rect, scale),
+            CanvasItem::StrokeRect(item) => item.get_handles(viewport),
+            CanvasItem::FilledRect(item) => item.get_handles(viewport),
+            CanvasItem::Arrow(item) => item.get_handles(viewport),
+            CanvasItem::Line(item) => item.get_handles(viewport),
+            CanvasItem::Mosaic(item) => item.get_handles(viewport),
+        }
+    }
+
+    /// スタイルの変更を適用（対応しない項目は無視）
+    pub fn apply_style_change(&mut self, change: StyleChange) {
+        match (self, change) {
+            (CanvasItem::Mosaic(item), StyleChange::MosaicGranularity(granularity)) => {
+                item.set_granularity(granularity)
+            }
+            // モザイクの粒度は線の太さとしては変更しない
+            (CanvasItem::Mosaic(_), _) => {}
+            (item, StyleChange::StrokeWidth(width)) => item.set_stroke_width(width),
+            (item, StyleChange::StrokeColor(color)) => item.set_stroke_color(color),
+            (item, StyleChange::FillColor(color)) => item.set_fill_color(color),
+            (item, StyleChange::Rounding(rounding)) => item.set_rounding(rounding),
+            (item, StyleChange::Opacity(opacity)) => item.set_opacity(opacity),
+            (_, StyleChange::MosaicGranularity(_)) => {}
+        }
+    }
+
+    pub fn apply_property_change(&mut self, change: PropertyChange) {
+        let metadata = self.metadata_mut();
+        match change {
+            PropertyChange::Label(label) => metadata.label = label,
+            PropertyChange::Note(note) => metadata.note = note,
+            PropertyChange::Tags(tags) => metadata.tags = tags,
+        }
+    }
+
+    /// 外接矩形（画像座標、左上と右下に正規化済み）。矩形のアイテムのみ
+    pub fn bounding_box(&self) -> Option<egui::Rect> {
+        let (x1, y1, x2, y2) = match self {
+            CanvasItem::StrokeRect(item) => (item.x1, item.y1, item.x2, item.y2),
+            CanvasItem::FilledRect(item) => (item.x1, item.y1, item.x2, item.y2),
+            CanvasItem::Mosaic(item) => (item.x1, item.y1, item.x2, item.y2),
+            _ => return None,
+        };
+        Some(egui::Rect::from_two_pos(
+            egui::pos2(x1, y1),
+            egui::pos2(x2, y2),
+        ))
+    }
+
+    pub fn metadata(&self) -> &ItemMetadata {
+        match self {
+            CanvasItem::StrokeRect(item) => &item.metadata,
+            CanvasItem::FilledRect(item) => &item.metadata,
+            CanvasItem::Arrow(item) => &item.metadata,
+            CanvasItem::Line(item) => &item.metadata,
+            CanvasItem::Mosaic(item) => &item.metadata,
+        }
+    }
+
+    pub fn metadata_mut(&mut self) -> &mut ItemMetadata {
+        match self {
+            CanvasItem::StrokeRect(item) => &mut item.metadata,
+            CanvasItem::FilledRect(item) => &mut item.metadata,
+            CanvasItem::Arrow(item) => &mut item.metadata,
+            CanvasItem::Line(item) => &mut item.metadata,
+            CanvasItem::Mosaic(item) => &mut item.metadata,
         }
     }
 
@@ -133,47 +276,129 @@ impl CanvasItem {
         }
     }
 
-    pub fn scale(&self, factor: f32) -> CanvasItem {
+    /// 不透明度（モザイクは隠す目的のため常に不透明で、None を返す）
+    pub fn get_opacity(&self) -> Option<f32> {
+        match self {
+            CanvasItem::StrokeRect(item) => Some(item.opacity),
+            CanvasItem::FilledRect(item) => Some(item.opacity),
+            CanvasItem::Arrow(item) => Some(item.opacity),
+            CanvasItem::Line(item) => Some(item.opacity),
+            CanvasItem::Mosaic(_) => None,
+        }
+    }
+
+    pub fn set_opacity(&mut self, opacity: f32) {
+        let opacity = opacity.clamp(0.0, 1.0);
+        match self {
+            CanvasItem::StrokeRect(item) => item.opacity = opacity,
+            CanvasItem::FilledRect(item) => item.opacity = opacity,
+            CanvasItem::Arrow(item) => item.opacity = opacity,
+            CanvasItem::Line(item) => item.opacity = opacity,
+            CanvasItem::Mosaic(_) => {}
+        }
+    }
+
+    /// 出力サイズに合わせて拡大縮小した複製（線の太さは縦横の倍率の相乗平均で変える）
+    pub fn scale(&self, factor: egui::Vec2) -> CanvasItem {
+        let width_factor = (factor.x * factor.y).sqrt();
         match self {
             CanvasItem::StrokeRect(item) => CanvasItem::StrokeRect(StrokeRect {
-                x1: item.x1 * factor,
-                y1: item.y1 * factor,
-                x2: item.x2 * factor,
-                y2: item.y2 * factor,
-                stroke_width: item.stroke_width * factor,
+                x1: item.x1 * factor.x,
+                y1: item.y1 * factor.y,
+                x2: item.x2 * factor.x,
+                y2: item.y2 * factor.y,
+                stroke_width: item.stroke_width * width_factor,
                 stroke_color: item.stroke_color,
+                opacity: item.opacity,
                 rounding: item.rounding,
+                metadata: item.metadata.clone(),
             }),
             CanvasItem::FilledRect(item) => CanvasItem::FilledRect(FilledRect {
-                x1: item.x1 * factor,
-                y1: item.y1 * factor,
-                x2: item.x2 * factor,
-                y2: item.y2 * factor,
+                x1: item.x1 * factor.x,
+                y1: item.y1 * factor.y,
+                x2: item.x2 * factor.x,
+                y2: item.y2 * factor.y,
                 filled_color: item.filled_color,
+                opacity: item.opacity,
                 rounding: item.rounding,
+                metadata: item.metadata.clone(),
             }),
             CanvasItem::Arrow(item) => CanvasItem::Arrow(Arrow {
-                start_x: item.start_x * factor,
-                start_y: item.start_y * factor,
-                end_x: item.end_x * factor,
-                end_y: item.end_y * factor,
+                start_x: item.start_x * factor.x,
+                start_y: item.start_y * factor.y,
+                end_x: item.end_x * factor.x,
+                end_y: item.end_y * factor.y,
                 color: item.color,
+                opacity: item.opacity,
+                metadata: item.metadata.clone(),
             }),
             CanvasItem::Line(item) => CanvasItem::Line(Line {
-                start_x: item.start_x * factor,
-                start_y: item.start_y * factor,
-                end_x: item.end_x * factor,
-                end_y: item.end_y * factor,
-                stroke_width: item.stroke_width * factor,
+                start_x: item.start_x * factor.x,
+                start_y: item.start_y * factor.y,
+                end_x: item.end_x * factor.x,
+                end_y: item.end_y * factor.y,
+                stroke_width: item.stroke_width * width_factor,
                 stroke_color: item.stroke_color,
+                opacity: item.opacity,
+                metadata: item.metadata.clone(),
             }),
             CanvasItem::Mosaic(item) => CanvasItem::Mosaic(Mosaic {
-                x1: item.x1 * factor,
-                y1: item.y1 * factor,
-                x2: item.x2 * factor,
-                y2: item.y2 * factor,
+                x1: item.x1 * factor.x,
+                y1: item.y1 * factor.y,
+                x2: item.x2 * factor.x,
+                y2: item.y2 * factor.y,
                 granularity: item.granularity,
+                metadata: item.metadata.clone(),
             }),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixmap_rgba(color: tiny_skia::Color) -> [u8; 4] {
+        let color = color.to_color_u8();
+        [color.red(), color.green(), color.blue(), color.alpha()]
+    }
+
+    fn assert_close(a: [u8; 4], b: [u8; 4]) {
+        let close = a.iter().zip(b).all(|(&a, b)| a.abs_diff(b) <= 1);
+        assert!(close, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn pixmap_color_scales_only_alpha() {
+        let color = egui::Color32::from_rgba_unmultiplied(255, 0, 0, 128);
+        assert_close(pixmap_rgba(pixmap_color(color, 0.5)), [255, 0, 0, 64]);
+    }
+
+    #[test]
+    fn canvas_and_export_colors_match() {
+        let colors = [
+            egui::Color32::from_rgba_unmultiplied(255, 0, 0, 128),
+            egui::Color32::from_rgba_unmultiplied(30, 144, 255, 255),
+            egui::Color32::from_rgba_unmultiplied(200, 180, 20, 77),
+        ];
+        for color in colors {
+            for opacity in [0.0, 0.25, 0.5, 0.8, 1.0] {
+                // 合成結果を決める乗算済みの値で比べる（乗算前の値は低い不透明度で誤差が大きい）
+                let canvas = with_opacity(color, opacity).to_array();
+                let export = pixmap_color(color, opacity).premultiply().to_color_u8();
+                let export = [export.red(), export.green(), export.blue(), export.alpha()];
+                assert_close(canvas, export);
+            }
+        }
+    }
+
+    #[test]
+    fn full_opacity_is_identity() {
+        let color = egui::Color32::from_rgba_unmultiplied(12, 34, 56, 200);
+        assert_eq!(with_opacity(color, 1.0), color);
+        assert_eq!(
+            pixmap_rgba(pixmap_color(color, 1.0)),
+            color.to_srgba_unmultiplied()
+        );
+    }
+}
@@ -1,4 +1,7 @@
-#[derive(Clone)]
+use crate::viewport::Viewport;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct Mosaic {
     pub x1: f32,
     pub y1: f32,
@@ -6,29 +9,22 @@ pub struct Mosaic {
     pub y2: f32,
 
     pub granularity: u8, // モザイクの粒度（ピクセル単位）
+    /// クラス名・メモ・タグ
+    #[serde(flatten)]
+    pub metadata: super::ItemMetadata,
 }
 
 impl Mosaic {
-    pub fn render(&self, ui: &mut egui::Ui, image_rect: egui::Rect, scale: f32) {
-        let world_min = image_rect.min
-            + (egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            } * scale)
-                .to_vec2();
-        let world_max = image_rect.min
-            + (egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            } * scale)
-                .to_vec2();
+    pub fn render(&self, ui: &mut egui::Ui, viewport: &Viewport) {
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         let world_rect = egui::Rect::from_min_max(world_min, world_max);
 
         // モザイク領域を半透明の灰色で表示
         ui.painter().rect_filled(
             world_rect,
             0.0,
-            egui::Color32::from_rgba_premultiplied(128, 128, 128, 100),
+            egui::Color32::from_rgba_unmultiplied(128, 128, 128, 100),
         );
 
         // 粒度を表示するための枠線
@@ -40,19 +36,9 @@ impl Mosaic {
         );
     }
 
-    pub fn hit_test(&self, pos: egui::Pos2, image_rect: egui::Rect, scale: f32) -> bool {
-        let world_min = image_rect.min
-            + (egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            } * scale)
-                .to_vec2();
-        let world_max = image_rect.min
-            + (egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            } * scale)
-                .to_vec2();
+    pub fn hit_test(&self, pos: egui::Pos2, viewport: &Viewport) -> bool {
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         let world_rect = egui::Rect::from_min_max(world_min, world_max);
         world_rect.contains(pos)
     }
@@ -97,24 +83,11 @@ impl Mosaic {
 
     pub fn get_handles(
         &self,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
     ) -> Vec<(egui::Pos2, crate::canvas_items::Handle)> {
         let mut handles = Vec::new();
-        let world_min = image_rect.min
-            + egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            }
-            .to_vec2()
-                * scale;
-        let world_max = image_rect.min
-            + egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            }
-            .to_vec2()
-                * scale;
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         handles.push((world_min, crate::canvas_items::Handle::Corner(0)));
         handles.push((
             egui::Pos2::new(world_max.x, world_min.y),
@@ -128,7 +101,7 @@ impl Mosaic {
         handles
     }
 
-    pub fn draw_on_pixmap(&self, pixmap: &mut tiny_skia::Pixmap, _image_bytes: &[u8]) {
+    pub fn draw_on_pixmap(&self, pixmap: &mut tiny_skia::Pixmap) {
         let width = pixmap.width() as usize;
         let height = pixmap.height() as usize;
 
@@ -169,14 +142,19 @@ impl Mosaic {
                     let avg_b = (b_sum / count) as u8;
                     let avg_a = (a_sum / count) as u8;
 
+                    // 乗算済みの値の平均なので、乗算し直さずにそのまま書き込む
+                    // （各チャンネルは不透明度の平均を超えないので変換は失敗しない）
+                    let Some(color) =
+                        tiny_skia::PremultipliedColorU8::from_rgba(avg_r, avg_g, avg_b, avg_a)
+                    else {
+                        continue;
+                    };
                     // ブロック内のすべてのピクセルを平均色で塗りつぶし
                     for py in by..((by + granularity).min(y2)) {
                         for px in bx..((bx + granularity).min(x2)) {
                             let idx = py * width + px;
                             if idx < pixmap.pixels_mut().len() {
-                                let color =
-                                    tiny_skia::Color::from_rgba8(avg_r, avg_g, avg_b, avg_a);
-                                pixmap.pixels_mut()[idx] = color.premultiply().to_color_u8();
+                                pixmap.pixels_mut()[idx] = color;
                             }
                         }
                     }
@@ -193,3 +171,52 @@ impl Mosaic {
         self.granularity = granularity.max(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mosaic(granularity: u8) -> Mosaic {
+        Mosaic {
+            x1: 0.0,
+            y1: 0.0,
+            x2: 4.0,
+            y2: 4.0,
+            granularity,
+            metadata: Default::default(),
+        }
+    }
+
+    fn demultiplied(pixel: tiny_skia::PremultipliedColorU8) -> [u8; 4] {
+        let color = pixel.demultiply();
+        [color.red(), color.green(), color.blue(), color.alpha()]
+    }
+
+    #[test]
+    fn keeps_color_of_half_transparent_source() {
+        let mut pixmap = tiny_skia::Pixmap::new(4, 4).unwrap();
+        let source = tiny_skia::ColorU8::from_rgba(200, 100, 50, 128).premultiply();
+        pixmap.pixels_mut().fill(source);
+        mosaic(2).draw_on_pixmap(&mut pixmap);
+        for pixel in pixmap.pixels() {
+            assert_eq!(*pixel, source);
+        }
+    }
+
+    #[test]
+    fn averages_premultiplied_pixels() {
+        // 左半分は不透明な赤、右半分は完全に透明
+        let mut pixmap = tiny_skia::Pixmap::new(4, 4).unwrap();
+        let red = tiny_skia::ColorU8::from_rgba(255, 0, 0, 255).premultiply();
+        for (index, pixel) in pixmap.pixels_mut().iter_mut().enumerate() {
+            if index % 4 < 2 {
+                *pixel = red;
+            }
+        }
+        mosaic(4).draw_on_pixmap(&mut pixmap);
+        // 色は赤のまま、不透明度だけが平均される
+        for pixel in pixmap.pixels() {
+            assert_eq!(demultiplied(*pixel), [255, 0, 0, 127]);
+        }
+    }
+}
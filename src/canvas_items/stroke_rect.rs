@@ -1,4 +1,7 @@
-#[derive(Clone)]
+use crate::viewport::Viewport;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
 pub struct StrokeRect {
     pub x1: f32,
     pub y1: f32,
@@ -7,50 +10,38 @@ pub struct StrokeRect {
 
     pub stroke_width: f32,
     pub stroke_color: egui::Color32,
+    #[serde(default = "super::default_opacity")]
+    pub opacity: f32,
     pub rounding: u8,
+    /// クラス名・メモ・タグ
+    #[serde(flatten)]
+    pub metadata: super::ItemMetadata,
 }
 
 impl StrokeRect {
-    pub fn render(&self, ui: &mut egui::Ui, image_rect: egui::Rect, scale: f32) {
-        let world_min = image_rect.min
-            + (egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            } * scale)
-                .to_vec2();
-        let world_max = image_rect.min
-            + (egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            } * scale)
-                .to_vec2();
+    pub fn render(&self, ui: &mut egui::Ui, viewport: &Viewport) {
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         let world_rect = egui::Rect::from_min_max(world_min, world_max);
 
-        let stroke_width = self.stroke_width * scale;
+        let stroke_width = viewport.image_len_to_screen(self.stroke_width);
         ui.painter().rect_stroke(
             world_rect,
             egui::CornerRadius::same(self.rounding),
-            egui::Stroke::new(stroke_width, self.stroke_color),
+            egui::Stroke::new(
+                stroke_width,
+                super::with_opacity(self.stroke_color, self.opacity),
+            ),
             egui::StrokeKind::Middle,
         );
     }
 
-    pub fn hit_test(&self, pos: egui::Pos2, image_rect: egui::Rect, scale: f32) -> bool {
-        let world_min = image_rect.min
-            + (egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            } * scale)
-                .to_vec2();
-        let world_max = image_rect.min
-            + (egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            } * scale)
-                .to_vec2();
+    pub fn hit_test(&self, pos: egui::Pos2, viewport: &Viewport) -> bool {
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         let world_rect = egui::Rect::from_min_max(world_min, world_max);
         // Expand by half stroke width for hit testing
-        let half_stroke = (self.stroke_width * scale) / 2.0;
+        let half_stroke = viewport.image_len_to_screen(self.stroke_width) / 2.0;
         let expanded_rect = world_rect.expand(half_stroke);
         expanded_rect.contains(pos)
     }
@@ -95,24 +86,11 @@ impl StrokeRect {
 
     pub fn get_handles(
         &self,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
     ) -> Vec<(egui::Pos2, crate::canvas_items::Handle)> {
         let mut handles = Vec::new();
-        let world_min = image_rect.min
-            + egui::Pos2 {
-                x: self.x1,
-                y: self.y1,
-            }
-            .to_vec2()
-                * scale;
-        let world_max = image_rect.min
-            + egui::Pos2 {
-                x: self.x2,
-                y: self.y2,
-            }
-            .to_vec2()
-                * scale;
+        let world_min = viewport.image_to_screen(egui::pos2(self.x1, self.y1));
+        let world_max = viewport.image_to_screen(egui::pos2(self.x2, self.y2));
         handles.push((world_min, crate::canvas_items::Handle::Corner(0)));
         handles.push((
             egui::Pos2::new(world_max.x, world_min.y),
@@ -128,12 +106,7 @@ impl StrokeRect {
 
     pub fn draw_on_pixmap(&self, pixmap: &mut tiny_skia::Pixmap) {
         let mut paint = tiny_skia::Paint::default();
-        paint.set_color_rgba8(
-            self.stroke_color.r(),
-            self.stroke_color.g(),
-            self.stroke_color.b(),
-            self.stroke_color.a(),
-        );
+        paint.set_color(super::pixmap_color(self.stroke_color, self.opacity));
         paint.anti_alias = true;
 
         let rect =
@@ -0,0 +1,79 @@
+use crate::canvas_items::CanvasItem;
+use serde::{Deserialize, Serialize};
+
+/// 他のタブから貼り付けられたテキストを判別するための識別子
+const CLIPBOARD_FORMAT: &str = "annoto-items";
+const CLIPBOARD_VERSION: u32 = 1;
+
+/// 貼り付けのたびにずらす量（画像座標）
+const PASTE_OFFSET: f32 = 10.0;
+
+/// システムクリップボードに書き出す形式
+#[derive(Serialize, Deserialize)]
+struct ClipboardPayload {
+    format: String,
+    version: u32,
+    items: Vec<CanvasItem>,
+}
+
+/// アイテムのコピー＆ペーストを管理（画像を切り替えても保持される）
+#[derive(Default)]
+pub struct ItemClipboard {
+    items: Vec<CanvasItem>,
+    paste_count: u32,
+}
+
+impl ItemClipboard {
+    /// アイテムを保持し、システムクリップボード用の文字列を返す
+    pub fn copy(&mut self, items: Vec<CanvasItem>) -> String {
+        let payload = ClipboardPayload {
+            format: CLIPBOARD_FORMAT.to_string(),
+            version: CLIPBOARD_VERSION,
+            items,
+        };
+        let text = serde_json::to_string(&payload).unwrap_or_default();
+        self.items = payload.items;
+        self.paste_count = 0;
+        text
+    }
+
+    /// 貼り付けるアイテムを返す
+    ///
+    /// システムクリップボードの内容が Annoto のアイテムであればそれを優先し、
+    /// そうでなければこのタブでコピーしたアイテムを使う。
+    pub fn paste(&mut self, text: Option<&str>) -> Vec<CanvasItem> {
+        if let Some(items) = text.and_then(Self::parse) {
+            // 別タブからの貼り付けは以後このタブのクリップボードとして扱う
+            if !Self::same_items(&items, &self.items) {
+                self.items = items;
+                self.paste_count = 0;
+            }
+        }
+        if self.items.is_empty() {
+            return Vec::new();
+        }
+
+        self.paste_count += 1;
+        let offset = PASTE_OFFSET * self.paste_count as f32;
+        self.items
+            .iter()
+            .map(|item| {
+                let mut item = item.clone();
+                item.translate(egui::vec2(offset, offset));
+                item
+            })
+            .collect()
+    }
+
+    fn parse(text: &str) -> Option<Vec<CanvasItem>> {
+        let payload: ClipboardPayload = serde_json::from_str(text).ok()?;
+        if payload.format != CLIPBOARD_FORMAT || payload.version > CLIPBOARD_VERSION {
+            return None;
+        }
+        Some(payload.items)
+    }
+
+    fn same_items(a: &[CanvasItem], b: &[CanvasItem]) -> bool {
+        serde_json::to_string(a).ok() == serde_json::to_string(b).ok()
+    }
+}
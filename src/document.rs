@@ -0,0 +1,214 @@
+use crate::canvas_items::CanvasItem;
+use crate::drawing::EdgeMap;
+use crate::error::{AppError, MAX_IMAGE_MEGAPIXELS};
+use crate::state::{SelectionState, ViewCommand};
+use crate::tiled_texture::TiledTexture;
+use crate::viewport::Viewport;
+use once_cell::unsync::OnceCell;
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::Arc;
+
+/// 読み込んだファイルの情報
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SourceFile {
+    pub name: String,
+    pub mime_type: String,
+    pub size_bytes: u64,
+    /// 最終更新日時（UNIX エポックからのミリ秒）
+    pub last_modified: f64,
+}
+
+/// デコード済みの画像（テクスチャ・スポイト・エクスポートで共有する）
+pub struct DecodedImage {
+    pub pixels: image::RgbaImage,
+    /// 元画像がアルファチャンネルを持つか
+    pub has_alpha: bool,
+}
+
+impl DecodedImage {
+    /// 画像データを RGBA にデコード
+    pub fn decode(name: &str, bytes: &[u8]) -> Result<Self, AppError> {
+        let decode_error = |e: image::ImageError| AppError::Decode {
+            name: name.to_string(),
+            reason: e.to_string(),
+        };
+        let reader = || {
+            image::io::Reader::new(Cursor::new(bytes))
+                .with_guessed_format()
+                .map_err(|e| decode_error(e.into()))
+        };
+        if reader()?.format().is_none() {
+            return Err(AppError::UnsupportedFormat {
+                name: name.to_string(),
+            });
+        }
+        // 巨大な画像は展開する前にサイズだけを確認して断る
+        let (width, height) = reader()?.into_dimensions().map_err(decode_error)?;
+        if width as u64 * height as u64 > MAX_IMAGE_MEGAPIXELS * 1_000_000 {
+            return Err(AppError::ImageTooLarge {
+                name: name.to_string(),
+                width,
+                height,
+            });
+        }
+        let img = reader()?.decode().map_err(decode_error)?;
+        let has_alpha = img.color().has_alpha();
+        Ok(Self {
+            // RGBA8 の画像はコピーせずにそのまま使う
+            pixels: img.into_rgba8(),
+            has_alpha,
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        self.pixels.width()
+    }
+
+    pub fn height(&self) -> u32 {
+        self.pixels.height()
+    }
+
+    /// 画像座標の位置の色
+    pub fn pixel_at(&self, pos: egui::Pos2) -> Option<egui::Color32> {
+        if pos.x < 0.0 || pos.y < 0.0 {
+            return None;
+        }
+        let (x, y) = (pos.x as u32, pos.y as u32);
+        if x >= self.width() || y >= self.height() {
+            return None;
+        }
+        let [r, g, b, a] = self.pixels.get_pixel(x, y).0;
+        Some(egui::Color32::from_rgba_unmultiplied(r, g, b, a))
+    }
+}
+
+/// 1枚の画像と、その上のアノテーション・表示状態
+pub struct Document {
+    pub id: u64,
+    pub source: SourceFile,
+    pub image_texture: TiledTexture,
+    /// 元ファイルのデータ（自動保存で使用）
+    pub image_bytes: Vec<u8>,
+    /// 実行中のエクスポートとも共有する
+    pub image: Arc<DecodedImage>,
+    /// 吸着に使うエッジ強度（大きな画像ではメモリを使うため、初めて吸着するときに作る）
+    edge_map: OnceCell<EdgeMap>,
+    pub rectangles: Vec<CanvasItem>,
+    pub selection_state: SelectionState,
+    /// 非表示中に保持しておく表示位置・倍率
+    pub viewport: Viewport,
+    pub view_command: Option<ViewCommand>,
+    /// 最後のエクスポート以降に変更があるか
+    pub dirty: bool,
+    /// アイテムを変更した回数（自動保存の要否の判定に使う）
+    pub revision: u64,
+    /// 自動保存で画像データを保存したキー（未保存なら None）
+    pub image_key: Option<String>,
+}
+
+impl Document {
+    /// 画像データをデコードしてドキュメントを作成
+    pub fn load(
+        ctx: &egui::Context,
+        id: u64,
+        source: SourceFile,
+        bytes: Vec<u8>,
+    ) -> Result<Self, AppError> {
+        let image = DecodedImage::decode(&source.name, &bytes)?;
+        let image_texture = TiledTexture::new(ctx, &format!("image_{}", id), &image.pixels);
+        Ok(Self {
+            id,
+            source,
+            image_texture,
+            image_bytes: bytes,
+            image: Arc::new(image),
+            edge_map: OnceCell::new(),
+            rectangles: Vec::new(),
+            selection_state: SelectionState::default(),
+            viewport: Viewport::default(),
+            view_command: Some(ViewCommand::ShrinkToFit),
+            dirty: false,
+            revision: 0,
+            image_key: None,
+        })
+    }
+
+    /// 画像のエッジ強度（初回の呼び出しで計算する）
+    pub fn edge_map(&self) -> &EdgeMap {
+        self.edge_map.get_or_init(|| {
+            EdgeMap::from_rgba(
+                self.image.width() as usize,
+                self.image.height() as usize,
+                self.image.pixels.as_raw(),
+            )
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.source.name
+    }
+
+    /// 元画像のサイズ（ピクセル）
+    pub fn image_size(&self) -> (u32, u32) {
+        (self.image.width(), self.image.height())
+    }
+
+    /// 選択中のアイテムの複製
+    pub fn selected_items(&self) -> Vec<CanvasItem> {
+        self.selection_state
+            .selected_items
+            .iter()
+            .filter_map(|&idx| self.rectangles.get(idx).cloned())
+            .collect()
+    }
+
+    /// アイテムを変更したときに呼び、未保存として扱う
+    pub fn mark_changed(&mut self) {
+        self.dirty = true;
+        self.revision += 1;
+    }
+
+    /// アイテムを追加して、追加したものを選択状態にする
+    pub fn add_items(&mut self, items: Vec<CanvasItem>) {
+        if items.is_empty() {
+            return;
+        }
+        let first = self.rectangles.len();
+        self.rectangles.extend(items);
+        self.selection_state.selected_items = (first..self.rectangles.len()).collect();
+        self.selection_state.selected_handle = None;
+        self.mark_changed();
+    }
+
+    pub fn translate_selected_items(&mut self, delta: egui::Vec2) {
+        if delta == egui::Vec2::ZERO {
+            return;
+        }
+        let mut moved = false;
+        for &idx in &self.selection_state.selected_items {
+            if let Some(item) = self.rectangles.get_mut(idx) {
+                item.translate(delta);
+                moved = true;
+            }
+        }
+        if moved {
+            self.mark_changed();
+        }
+    }
+
+    pub fn delete_selected_items(&mut self) {
+        let mut indices = self.selection_state.selected_items.clone();
+        indices.sort_unstable();
+        let count = self.rectangles.len();
+        for idx in indices.into_iter().rev() {
+            if idx < self.rectangles.len() {
+                self.rectangles.remove(idx);
+            }
+        }
+        self.selection_state.clear();
+        if self.rectangles.len() != count {
+            self.mark_changed();
+        }
+    }
+}
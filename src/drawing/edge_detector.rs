@@ -0,0 +1,147 @@
+use egui;
+
+/// エッジとみなす平均強度の下限
+const MIN_EDGE_STRENGTH: f32 = 24.0;
+
+/// Sobel フィルタで求めた画像のエッジ強度マップ
+pub struct EdgeMap {
+    width: usize,
+    height: usize,
+    magnitude: Vec<u8>,
+}
+
+impl EdgeMap {
+    /// RGBA バッファからエッジ強度を計算
+    pub fn from_rgba(width: usize, height: usize, rgba: &[u8]) -> Self {
+        // 輝度に変換（透明部分は暗く扱う）。巨大な画像でもメモリを抑えるため 1 ピクセル 1 バイトで持つ
+        let luma: Vec<u8> = rgba
+            .chunks_exact(4)
+            .map(|p| {
+                let y = (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000;
+                (y * p[3] as u32 / 255) as u8
+            })
+            .collect();
+        let luma = |index: usize| luma[index] as i32;
+
+        let mut magnitude = vec![0u8; width * height];
+        if width >= 3 && height >= 3 {
+            for y in 1..height - 1 {
+                let row_above = (y - 1) * width;
+                let row = y * width;
+                let row_below = (y + 1) * width;
+                for x in 1..width - 1 {
+                    let gx =
+                        (luma(row_above + x + 1) + 2 * luma(row + x + 1) + luma(row_below + x + 1))
+                            - (luma(row_above + x - 1)
+                                + 2 * luma(row + x - 1)
+                                + luma(row_below + x - 1));
+                    let gy = (luma(row_below + x - 1)
+                        + 2 * luma(row_below + x)
+                        + luma(row_below + x + 1))
+                        - (luma(row_above + x - 1)
+                            + 2 * luma(row_above + x)
+                            + luma(row_above + x + 1));
+                    // |gx| + |gy| の最大値 2040 を 0..=255 に収める
+                    magnitude[row + x] = ((gx.abs() + gy.abs()) / 8).min(255) as u8;
+                }
+            }
+        }
+
+        Self {
+            width,
+            height,
+            magnitude,
+        }
+    }
+
+    /// 矩形の各辺を許容範囲内で最も強いエッジへ吸着させる（画像座標）
+    pub fn snap_rect(
+        &self,
+        min: egui::Pos2,
+        max: egui::Pos2,
+        tolerance: f32,
+    ) -> (egui::Pos2, egui::Pos2) {
+        if self.width == 0 || self.height == 0 {
+            return (min, max);
+        }
+        let (y_from, y_to) = self.span(min.y, max.y, self.height);
+        let (x_from, x_to) = self.span(min.x, max.x, self.width);
+
+        let left = self.snap_vertical_edge(min.x, y_from, y_to, tolerance);
+        let right = self.snap_vertical_edge(max.x, y_from, y_to, tolerance);
+        let top = self.snap_horizontal_edge(min.y, x_from, x_to, tolerance);
+        let bottom = self.snap_horizontal_edge(max.y, x_from, x_to, tolerance);
+
+        // 吸着の結果、矩形が潰れる場合は元の値を使う
+        let (x1, x2) = if left < right {
+            (left, right)
+        } else {
+            (min.x, max.x)
+        };
+        let (y1, y2) = if top < bottom {
+            (top, bottom)
+        } else {
+            (min.y, max.y)
+        };
+        (egui::pos2(x1, y1), egui::pos2(x2, y2))
+    }
+
+    fn span(&self, from: f32, to: f32, limit: usize) -> (usize, usize) {
+        let from = (from.max(0.0) as usize).min(limit - 1);
+        let to = (to.max(0.0) as usize).min(limit - 1);
+        (from, to.max(from))
+    }
+
+    /// 縦の辺（x 座標）を吸着させる
+    fn snap_vertical_edge(&self, x: f32, y_from: usize, y_to: usize, tolerance: f32) -> f32 {
+        self.snap_edge(x, tolerance, self.width, |cx| {
+            let sum: u32 = (y_from..=y_to)
+                .map(|y| self.magnitude[y * self.width + cx] as u32)
+                .sum();
+            sum as f32 / (y_to - y_from + 1) as f32
+        })
+    }
+
+    /// 横の辺（y 座標）を吸着させる
+    fn snap_horizontal_edge(&self, y: f32, x_from: usize, x_to: usize, tolerance: f32) -> f32 {
+        self.snap_edge(y, tolerance, self.height, |cy| {
+            let row = cy * self.width;
+            let sum: u32 = self.magnitude[row + x_from..=row + x_to]
+                .iter()
+                .map(|&m| m as u32)
+                .sum();
+            sum as f32 / (x_to - x_from + 1) as f32
+        })
+    }
+
+    fn snap_edge(
+        &self,
+        value: f32,
+        tolerance: f32,
+        limit: usize,
+        strength_at: impl Fn(usize) -> f32,
+    ) -> f32 {
+        let from = (value - tolerance).round().max(0.0) as usize;
+        let to = ((value + tolerance).round().max(0.0) as usize).min(limit - 1);
+
+        let mut best: Option<(usize, f32)> = None;
+        for candidate in from..=to {
+            let strength = strength_at(candidate);
+            if strength < MIN_EDGE_STRENGTH {
+                continue;
+            }
+            let is_better = match best {
+                Some((best_pos, best_strength)) => {
+                    strength > best_strength
+                        || (strength == best_strength
+                            && (candidate as f32 - value).abs() < (best_pos as f32 - value).abs())
+                }
+                None => true,
+            };
+            if is_better {
+                best = Some((candidate, strength));
+            }
+        }
+        best.map(|(pos, _)| pos as f32).unwrap_or(value)
+    }
+}
@@ -1,41 +1,78 @@
 use crate::canvas_items::CanvasItem;
+use crate::viewport::Viewport;
 use egui;
 
+/// ハンドルに対して行われた操作
+pub enum HandleAction {
+    /// ハンドルのドラッグでアイテムの大きさが変わった
+    Resized,
+    /// 削除ハンドルが押された
+    Delete,
+}
+
 pub struct ItemRenderer;
 
 impl ItemRenderer {
     /// 既存のアイテムを描画
     pub fn render_existing_items(
         ui: &mut egui::Ui,
-        rectangles: &mut [CanvasItem],
-        image_rect: egui::Rect,
-        scale: f32,
+        rectangles: &[CanvasItem],
+        viewport: &Viewport,
     ) {
-        for item in rectangles.iter_mut() {
+        for item in rectangles.iter() {
             match item {
-                CanvasItem::StrokeRect(rect) => rect.render(ui, image_rect, scale),
-                CanvasItem::FilledRect(rect) => rect.render(ui, image_rect, scale),
-                CanvasItem::Arrow(arrow) => arrow.render(ui, image_rect, scale),
-                CanvasItem::Line(line) => line.render(ui, image_rect, scale),
-                CanvasItem::Mosaic(mosaic) => mosaic.render(ui, image_rect, scale),
+                CanvasItem::StrokeRect(rect) => rect.render(ui, viewport),
+                CanvasItem::FilledRect(rect) => rect.render(ui, viewport),
+                CanvasItem::Arrow(arrow) => arrow.render(ui, viewport),
+                CanvasItem::Line(line) => line.render(ui, viewport),
+                CanvasItem::Mosaic(mosaic) => mosaic.render(ui, viewport),
             };
         }
     }
 
+    /// 複数選択中のアイテムを枠で囲んで表示
+    pub fn render_selection_outlines(
+        ui: &mut egui::Ui,
+        selected_items: &[usize],
+        rectangles: &[CanvasItem],
+        viewport: &Viewport,
+    ) {
+        // 単一選択時はハンドルで表示するため枠は描かない
+        if selected_items.len() < 2 {
+            return;
+        }
+        for &idx in selected_items {
+            if let Some(item) = rectangles.get(idx) {
+                let bounds = egui::Rect::from_points(
+                    &item
+                        .get_handles(viewport)
+                        .into_iter()
+                        .map(|(pos, _)| pos)
+                        .collect::<Vec<_>>(),
+                );
+                ui.painter().rect_stroke(
+                    bounds.expand(4.0),
+                    0.0,
+                    egui::Stroke::new(1.0, egui::Color32::BLUE),
+                    egui::StrokeKind::Outside,
+                );
+            }
+        }
+    }
+
     /// ハンドルを描画
     pub fn render_handles(
         ui: &mut egui::Ui,
         selected_item: Option<usize>,
         selected_handle: &mut Option<crate::canvas_items::Handle>,
         rectangles: &mut [CanvasItem],
-        image_rect: egui::Rect,
-        scale: f32,
-    ) -> bool {
-        let mut should_delete = false;
+        viewport: &Viewport,
+    ) -> Option<HandleAction> {
+        let mut action = None;
         if let Some(selected_idx) = selected_item {
             // ハンドル情報を先に取得
             let (handles, is_rect) = if let Some(item) = rectangles.get(selected_idx) {
-                let handles = item.get_handles(image_rect, scale);
+                let handles = item.get_handles(viewport);
                 let is_rect = matches!(
                     item,
                     CanvasItem::StrokeRect(_) | CanvasItem::FilledRect(_) | CanvasItem::Mosaic(_)
@@ -60,9 +97,10 @@ impl ItemRenderer {
                     *selected_handle = Some(handle.clone());
                 }
                 if response.dragged() {
-                    let delta = response.drag_delta() / scale;
+                    let delta = viewport.screen_delta_to_image(response.drag_delta());
                     if let Some(item_mut) = rectangles.get_mut(selected_idx) {
                         item_mut.resize(&handle, delta);
+                        action = Some(HandleAction::Resized);
                     }
                 }
 
@@ -105,9 +143,9 @@ impl ItemRenderer {
                 egui::Color32::WHITE,
             );
             if response.clicked() {
-                should_delete = true;
+                action = Some(HandleAction::Delete);
             }
         }
-        should_delete
+        action
     }
 }
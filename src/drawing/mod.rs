@@ -1,7 +1,9 @@
+pub mod edge_detector;
 pub mod item_renderer;
 pub mod preview_renderer;
 pub mod shape_factory;
 
-pub use item_renderer::ItemRenderer;
+pub use edge_detector::EdgeMap;
+pub use item_renderer::{HandleAction, ItemRenderer};
 pub use preview_renderer::PreviewRenderer;
 pub use shape_factory::ShapeFactory;
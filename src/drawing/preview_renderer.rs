@@ -1,6 +1,8 @@
 use crate::canvas_items::*;
+use crate::drawing::{EdgeMap, ShapeFactory};
 use crate::drawing_tool::DrawingTool;
 use crate::state::DrawingState;
+use crate::viewport::Viewport;
 use egui;
 
 pub struct PreviewRenderer;
@@ -10,8 +12,8 @@ impl PreviewRenderer {
     pub fn render_drag_preview(
         ui: &mut egui::Ui,
         drawing_state: &DrawingState,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
+        edge_map: Option<&EdgeMap>,
     ) {
         let Some(start_world) = drawing_state.drag_start else {
             return;
@@ -19,101 +21,102 @@ impl PreviewRenderer {
         let Some(end_world) = ui.input(|i| i.pointer.hover_pos()) else {
             return;
         };
-        if !image_rect.contains(end_world) {
+        if !viewport.image_screen_rect().contains(end_world) {
             return;
         }
 
+        let style = drawing_state.style();
         match drawing_state.current_tool {
             DrawingTool::StrokeRect => {
-                let min_world = egui::pos2(
-                    start_world.x.min(end_world.x),
-                    start_world.y.min(end_world.y),
+                let (offset_min, offset_max) = ShapeFactory::drag_rect(
+                    start_world,
+                    end_world,
+                    viewport,
+                    drawing_state,
+                    edge_map,
                 );
-                let max_world = egui::pos2(
-                    start_world.x.max(end_world.x),
-                    start_world.y.max(end_world.y),
-                );
-                let offset_min = (min_world - image_rect.min) / scale;
-                let offset_max = (max_world - image_rect.min) / scale;
 
                 let preview = StrokeRect {
                     x1: offset_min.x,
                     y1: offset_min.y,
                     x2: offset_max.x,
                     y2: offset_max.y,
-                    stroke_width: drawing_state.stroke_width,
-                    stroke_color: drawing_state.stroke_color,
-                    rounding: drawing_state.rounding,
+                    stroke_width: style.stroke_width,
+                    stroke_color: style.stroke_color,
+                    opacity: style.opacity,
+                    rounding: style.rounding,
+                    metadata: ItemMetadata::default(),
                 };
-                preview.render(ui, image_rect, scale);
+                preview.render(ui, viewport);
             }
             DrawingTool::FilledRect => {
-                let min_world = egui::pos2(
-                    start_world.x.min(end_world.x),
-                    start_world.y.min(end_world.y),
-                );
-                let max_world = egui::pos2(
-                    start_world.x.max(end_world.x),
-                    start_world.y.max(end_world.y),
+                let (offset_min, offset_max) = ShapeFactory::drag_rect(
+                    start_world,
+                    end_world,
+                    viewport,
+                    drawing_state,
+                    edge_map,
                 );
-                let offset_min = (min_world - image_rect.min) / scale;
-                let offset_max = (max_world - image_rect.min) / scale;
 
                 let preview = FilledRect {
                     x1: offset_min.x,
                     y1: offset_min.y,
                     x2: offset_max.x,
                     y2: offset_max.y,
-                    filled_color: drawing_state.fill_color,
-                    rounding: drawing_state.rounding,
+                    filled_color: style.fill_color,
+                    opacity: style.opacity,
+                    rounding: style.rounding,
+                    metadata: ItemMetadata::default(),
                 };
-                preview.render(ui, image_rect, scale);
+                preview.render(ui, viewport);
             }
             DrawingTool::Arrow => {
-                let offset_start = (start_world - image_rect.min) / scale;
-                let offset_end = (end_world - image_rect.min) / scale;
+                let offset_start = viewport.screen_to_image(start_world);
+                let offset_end = viewport.screen_to_image(end_world);
                 let preview = Arrow {
                     start_x: offset_start.x,
                     start_y: offset_start.y,
                     end_x: offset_end.x,
                     end_y: offset_end.y,
-                    color: drawing_state.stroke_color,
+                    color: style.stroke_color,
+                    opacity: style.opacity,
+                    metadata: ItemMetadata::default(),
                 };
-                preview.render(ui, image_rect, scale);
+                preview.render(ui, viewport);
             }
             DrawingTool::Line => {
-                let offset_start = (start_world - image_rect.min) / scale;
-                let offset_end = (end_world - image_rect.min) / scale;
+                let offset_start = viewport.screen_to_image(start_world);
+                let offset_end = viewport.screen_to_image(end_world);
                 let preview = Line {
                     start_x: offset_start.x,
                     start_y: offset_start.y,
                     end_x: offset_end.x,
                     end_y: offset_end.y,
-                    stroke_width: drawing_state.stroke_width,
-                    stroke_color: drawing_state.stroke_color,
+                    stroke_width: style.stroke_width,
+                    stroke_color: style.stroke_color,
+                    opacity: style.opacity,
+                    metadata: ItemMetadata::default(),
                 };
-                preview.render(ui, image_rect, scale);
+                preview.render(ui, viewport);
             }
             DrawingTool::Mosaic => {
-                let min_world = egui::pos2(
-                    start_world.x.min(end_world.x),
-                    start_world.y.min(end_world.y),
-                );
-                let max_world = egui::pos2(
-                    start_world.x.max(end_world.x),
-                    start_world.y.max(end_world.y),
+                let (offset_min, offset_max) = ShapeFactory::drag_rect(
+                    start_world,
+                    end_world,
+                    viewport,
+                    drawing_state,
+                    edge_map,
                 );
-                let offset_min = (min_world - image_rect.min) / scale;
-                let offset_max = (max_world - image_rect.min) / scale;
 
                 let preview = Mosaic {
                     x1: offset_min.x,
                     y1: offset_min.y,
                     x2: offset_max.x,
                     y2: offset_max.y,
-                    granularity: drawing_state.mosaic_granularity,
+                    granularity: style.mosaic_granularity,
+                    metadata: ItemMetadata::default(),
                 };
-                preview.render(ui, image_rect, scale);
+                preview.render(ui, viewport);
             }
         }
     }
@@ -1,6 +1,8 @@
 use crate::canvas_items::*;
+use crate::drawing::EdgeMap;
 use crate::drawing_tool::DrawingTool;
 use crate::state::DrawingState;
+use crate::viewport::Viewport;
 use egui;
 
 pub struct ShapeFactory;
@@ -11,76 +13,100 @@ impl ShapeFactory {
         tool: DrawingTool,
         start: egui::Pos2,
         end: egui::Pos2,
-        image_rect: egui::Rect,
-        scale: f32,
+        viewport: &Viewport,
         drawing_state: &DrawingState,
+        edge_map: Option<&EdgeMap>,
     ) -> Option<CanvasItem> {
+        let style = drawing_state.styles.get(tool);
         match tool {
             DrawingTool::StrokeRect => {
-                let min = egui::pos2(start.x.min(end.x), start.y.min(end.y));
-                let max = egui::pos2(start.x.max(end.x), start.y.max(end.y));
-                let offset_min = (min - image_rect.min) / scale;
-                let offset_max = (max - image_rect.min) / scale;
+                let (offset_min, offset_max) =
+                    Self::drag_rect(start, end, viewport, drawing_state, edge_map);
                 Some(CanvasItem::StrokeRect(StrokeRect {
                     x1: offset_min.x,
                     y1: offset_min.y,
                     x2: offset_max.x,
                     y2: offset_max.y,
-                    stroke_width: drawing_state.stroke_width,
-                    stroke_color: drawing_state.stroke_color,
-                    rounding: drawing_state.rounding,
+                    stroke_width: style.stroke_width,
+                    stroke_color: style.stroke_color,
+                    opacity: style.opacity,
+                    rounding: style.rounding,
+                    metadata: ItemMetadata::default(),
                 }))
             }
             DrawingTool::FilledRect => {
-                let min = egui::pos2(start.x.min(end.x), start.y.min(end.y));
-                let max = egui::pos2(start.x.max(end.x), start.y.max(end.y));
-                let offset_min = (min - image_rect.min) / scale;
-                let offset_max = (max - image_rect.min) / scale;
+                let (offset_min, offset_max) =
+                    Self::drag_rect(start, end, viewport, drawing_state, edge_map);
                 Some(CanvasItem::FilledRect(FilledRect {
                     x1: offset_min.x,
                     y1: offset_min.y,
                     x2: offset_max.x,
                     y2: offset_max.y,
-                    filled_color: drawing_state.fill_color,
-                    rounding: drawing_state.rounding,
+                    filled_color: style.fill_color,
+                    opacity: style.opacity,
+                    rounding: style.rounding,
+                    metadata: ItemMetadata::default(),
                 }))
             }
             DrawingTool::Arrow => {
-                let offset_start = (start - image_rect.min) / scale;
-                let offset_end = (end - image_rect.min) / scale;
+                let offset_start = viewport.screen_to_image(start);
+                let offset_end = viewport.screen_to_image(end);
                 Some(CanvasItem::Arrow(Arrow {
                     start_x: offset_start.x,
                     start_y: offset_start.y,
                     end_x: offset_end.x,
                     end_y: offset_end.y,
-                    color: drawing_state.stroke_color,
+                    color: style.stroke_color,
+                    opacity: style.opacity,
+                    metadata: ItemMetadata::default(),
                 }))
             }
             DrawingTool::Line => {
-                let offset_start = (start - image_rect.min) / scale;
-                let offset_end = (end - image_rect.min) / scale;
+                let offset_start = viewport.screen_to_image(start);
+                let offset_end = viewport.screen_to_image(end);
                 Some(CanvasItem::Line(Line {
                     start_x: offset_start.x,
                     start_y: offset_start.y,
                     end_x: offset_end.x,
                     end_y: offset_end.y,
-                    stroke_width: drawing_state.stroke_width,
-                    stroke_color: drawing_state.stroke_color,
+                    stroke_width: style.stroke_width,
+                    stroke_color: style.stroke_color,
+                    opacity: style.opacity,
+                    metadata: ItemMetadata::default(),
                 }))
             }
             DrawingTool::Mosaic => {
-                let min = egui::pos2(start.x.min(end.x), start.y.min(end.y));
-                let max = egui::pos2(start.x.max(end.x), start.y.max(end.y));
-                let offset_min = (min - image_rect.min) / scale;
-                let offset_max = (max - image_rect.min) / scale;
+                let (offset_min, offset_max) =
+                    Self::drag_rect(start, end, viewport, drawing_state, edge_map);
                 Some(CanvasItem::Mosaic(Mosaic {
                     x1: offset_min.x,
                     y1: offset_min.y,
                     x2: offset_max.x,
                     y2: offset_max.y,
-                    granularity: drawing_state.mosaic_granularity,
+                    granularity: style.mosaic_granularity,
+                    metadata: ItemMetadata::default(),
                 }))
             }
         }
     }
+
+    /// ドラッグ範囲を画像座標の矩形に変換（マグネット吸着が有効ならエッジに合わせる）
+    pub fn drag_rect(
+        start: egui::Pos2,
+        end: egui::Pos2,
+        viewport: &Viewport,
+        drawing_state: &DrawingState,
+        edge_map: Option<&EdgeMap>,
+    ) -> (egui::Pos2, egui::Pos2) {
+        let min = egui::pos2(start.x.min(end.x), start.y.min(end.y));
+        let max = egui::pos2(start.x.max(end.x), start.y.max(end.y));
+        let offset_min = viewport.screen_to_image(min);
+        let offset_max = viewport.screen_to_image(max);
+        match edge_map {
+            Some(edge_map) if drawing_state.magnetic_snap => {
+                edge_map.snap_rect(offset_min, offset_max, drawing_state.snap_tolerance)
+            }
+            _ => (offset_min, offset_max),
+        }
+    }
 }
@@ -1,4 +1,6 @@
-#[derive(Clone, Copy)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum DrawingTool {
     StrokeRect,
     FilledRect,
@@ -6,3 +8,23 @@ pub enum DrawingTool {
     Line,
     Mosaic,
 }
+
+impl DrawingTool {
+    pub const ALL: [DrawingTool; 5] = [
+        DrawingTool::StrokeRect,
+        DrawingTool::FilledRect,
+        DrawingTool::Arrow,
+        DrawingTool::Line,
+        DrawingTool::Mosaic,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DrawingTool::StrokeRect => "四角形",
+            DrawingTool::FilledRect => "塗りつぶし四角形",
+            DrawingTool::Arrow => "矢印",
+            DrawingTool::Line => "直線",
+            DrawingTool::Mosaic => "モザイク",
+        }
+    }
+}
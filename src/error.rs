@@ -0,0 +1,96 @@
+use std::fmt;
+use wasm_bindgen::{JsCast, JsValue};
+
+/// 開ける画像の最大画素数（メガピクセル）
+pub const MAX_IMAGE_MEGAPIXELS: u64 = 100;
+
+/// 画面に通知する読み込み・書き出しの失敗
+#[derive(Debug, Clone)]
+pub enum AppError {
+    /// ファイルを読み込めなかった
+    Load { name: String },
+    /// 画像として判別できない形式
+    UnsupportedFormat { name: String },
+    /// 画素数が上限を超えている
+    ImageTooLarge {
+        name: String,
+        width: u32,
+        height: u32,
+    },
+    /// 画像データが壊れている
+    Decode { name: String, reason: String },
+    /// 画像や ZIP を書き出せなかった
+    Encode { name: String, reason: String },
+    /// ブラウザでの JPEG 圧縮に失敗し、アプリ内での圧縮に切り替えた
+    EncodeFallback { name: String, reason: String },
+    /// ダウンロードを開始できなかった
+    Download { name: String, reason: String },
+    /// エクスポートする画像がない
+    NoImage,
+    /// プリセットファイルを読み込めなかった
+    PresetImport { reason: String },
+    /// COCO / Pascal VOC のアノテーションを読み込めなかった
+    AnnotationImport { reason: String },
+    /// 自動保存に失敗した
+    AutoSave { reason: String },
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppError::Load { name } => write!(f, "{} を読み込めませんでした", name),
+            AppError::UnsupportedFormat { name } => write!(
+                f,
+                "{} は対応していない形式です（PNG や JPEG などの画像を選んでください）",
+                name
+            ),
+            AppError::ImageTooLarge {
+                name,
+                width,
+                height,
+            } => write!(
+                f,
+                "{} は大きすぎるため開けません（{} x {} px、上限 {} Mpx）",
+                name, width, height, MAX_IMAGE_MEGAPIXELS
+            ),
+            AppError::Decode { name, reason } => {
+                write!(f, "{} の画像データを読み取れませんでした: {}", name, reason)
+            }
+            AppError::Encode { name, reason } => {
+                write!(f, "{} の書き出しに失敗しました: {}", name, reason)
+            }
+            AppError::EncodeFallback { name, reason } => write!(
+                f,
+                "{} をブラウザで JPEG に圧縮できなかったため、アプリ内で圧縮しました: {}",
+                name, reason
+            ),
+            AppError::Download { name, reason } => {
+                write!(
+                    f,
+                    "{} のダウンロードを開始できませんでした: {}",
+                    name, reason
+                )
+            }
+            AppError::NoImage => write!(f, "エクスポートする画像がありません"),
+            AppError::PresetImport { reason } => {
+                write!(f, "プリセットを読み込めませんでした: {}", reason)
+            }
+            AppError::AnnotationImport { reason } => {
+                write!(f, "アノテーションを読み込めませんでした: {}", reason)
+            }
+            AppError::AutoSave { reason } => {
+                write!(f, "セッションを自動保存できませんでした: {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+/// JavaScript の例外を通知に表示する文字列にする
+pub fn js_error_reason(error: &JsValue) -> String {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => String::from(error.message()),
+        None => error.as_string().unwrap_or_else(|| format!("{:?}", error)),
+    }
+}
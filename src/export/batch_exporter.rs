@@ -0,0 +1,146 @@
+use crate::document::Document;
+use crate::error::AppError;
+use crate::export::{ExportProgress, FileNameTemplate, ImageExportJob};
+use crate::state::UiState;
+use std::collections::VecDeque;
+use std::io::{Cursor, Write};
+use zip::CompressionMethod;
+use zip::ZipWriter;
+use zip::write::SimpleFileOptions;
+
+/// 一括エクスポートで作成する ZIP のファイル名
+pub const BATCH_ZIP_FILENAME: &str = "annotated_images.zip";
+
+/// 出力待ちの1枚
+struct BatchEntry {
+    name: String,
+    filename: String,
+    job: ImageExportJob,
+}
+
+/// 全ドキュメントを同じ設定でエクスポートし、1つの ZIP にまとめる
+pub struct BatchExportJob {
+    entries: VecDeque<BatchEntry>,
+    total: usize,
+    zip: Option<ZipWriter<Cursor<Vec<u8>>>>,
+    /// 完了した画像で発生した、出力は続けられた問題
+    notices: Vec<AppError>,
+}
+
+impl BatchExportJob {
+    pub fn new(documents: &[Document], ui_state: &UiState) -> Self {
+        let mut used_names: Vec<String> = Vec::new();
+        let date = FileNameTemplate::today();
+        let mut entries = VecDeque::new();
+        for document in documents {
+            let filename = Self::unique_name(
+                FileNameTemplate::for_document(document, ui_state, &date),
+                &used_names,
+            );
+            used_names.push(filename.clone());
+            entries.push_back(BatchEntry {
+                name: document.name().to_string(),
+                filename,
+                job: ImageExportJob::for_document(document, ui_state),
+            });
+        }
+        Self {
+            total: entries.len(),
+            entries,
+            zip: Some(ZipWriter::new(Cursor::new(Vec::new()))),
+            notices: Vec::new(),
+        }
+    }
+
+    /// 処理を進め、すべての画像を書き込んだら ZIP のデータを返す
+    pub fn step(&mut self) -> Option<Result<Vec<u8>, AppError>> {
+        let Some(entry) = self.entries.front_mut() else {
+            return Some(self.finish());
+        };
+        let result = entry.job.step()?;
+        if let Some(reason) = entry.job.take_fallback_reason() {
+            self.notices.push(AppError::EncodeFallback {
+                name: entry.name.clone(),
+                reason,
+            });
+        }
+        let data = match result {
+            Ok(data) => data,
+            Err(reason) => {
+                return Some(Err(AppError::Encode {
+                    name: entry.name.clone(),
+                    reason,
+                }));
+            }
+        };
+        let zip = self.zip.as_mut()?;
+        // PNG/JPEG は圧縮済みなので無圧縮で格納する
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let written = zip
+            .start_file(entry.filename.as_str(), options)
+            .map_err(Self::zip_error)
+            .and_then(|_| zip.write_all(&data).map_err(Self::zip_error));
+        if let Err(e) = written {
+            return Some(Err(e));
+        }
+        self.entries.pop_front();
+        None
+    }
+
+    /// 通知する問題を取り出す
+    pub fn take_notices(&mut self) -> Vec<AppError> {
+        std::mem::take(&mut self.notices)
+    }
+
+    pub fn progress(&self) -> ExportProgress {
+        let completed = self.total - self.entries.len();
+        let (fraction, label) = match self.entries.front() {
+            Some(entry) => (
+                entry.job.progress(),
+                format!(
+                    "{} ({}/{}): {}",
+                    entry.job.stage_label(),
+                    completed + 1,
+                    self.total,
+                    entry.name
+                ),
+            ),
+            None => (0.0, "ZIP を作成中".to_string()),
+        };
+        ExportProgress {
+            fraction: (completed as f32 + fraction) / self.total.max(1) as f32,
+            label,
+        }
+    }
+
+    fn finish(&mut self) -> Result<Vec<u8>, AppError> {
+        let zip = self
+            .zip
+            .take()
+            .ok_or_else(|| Self::zip_error("already finished"))?;
+        let cursor = zip.finish().map_err(Self::zip_error)?;
+        Ok(cursor.into_inner())
+    }
+
+    fn zip_error(e: impl std::fmt::Display) -> AppError {
+        AppError::Encode {
+            name: BATCH_ZIP_FILENAME.to_string(),
+            reason: e.to_string(),
+        }
+    }
+
+    /// 同名のファイルがある場合は連番を付ける
+    fn unique_name(name: String, used_names: &[String]) -> String {
+        if !used_names.contains(&name) {
+            return name;
+        }
+        let (stem, ext) = match name.rsplit_once('.') {
+            Some((stem, ext)) => (stem.to_string(), format!(".{}", ext)),
+            None => (name.clone(), String::new()),
+        };
+        (2..)
+            .map(|n| format!("{}_{}{}", stem, n, ext))
+            .find(|candidate| !used_names.contains(candidate))
+            .unwrap()
+    }
+}
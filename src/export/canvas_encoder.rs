@@ -0,0 +1,87 @@
+use crate::error::js_error_reason;
+use image::RgbaImage;
+use std::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::Clamped;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+/// エンコードしたデータ、または失敗の理由
+type EncodeResult = Result<Vec<u8>, String>;
+
+/// ブラウザで実行中のエンコードの結果（完了するまで None）
+#[derive(Clone, Default)]
+pub struct PendingEncode(Rc<RefCell<Option<EncodeResult>>>);
+
+impl PendingEncode {
+    /// 完了していれば結果を取り出す
+    pub fn take(&self) -> Option<EncodeResult> {
+        self.0.borrow_mut().take()
+    }
+}
+
+/// 分割できない JPEG の圧縮をブラウザの canvas に任せ、UI を止めないようにする
+pub struct CanvasEncoder;
+
+impl CanvasEncoder {
+    /// JPEG への圧縮を開始する（canvas を使えない場合はその理由を返す）
+    pub fn start_jpeg(rgba: &RgbaImage, quality: u8) -> Result<PendingEncode, String> {
+        Self::start(rgba, "image/jpeg", quality as f64 / 100.0).map_err(|e| js_error_reason(&e))
+    }
+
+    fn start(rgba: &RgbaImage, mime_type: &str, quality: f64) -> Result<PendingEncode, JsValue> {
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("document is unavailable")?;
+        let canvas = document
+            .create_element("canvas")?
+            .dyn_into::<web_sys::HtmlCanvasElement>()?;
+        canvas.set_width(rgba.width());
+        canvas.set_height(rgba.height());
+        let context = canvas
+            .get_context("2d")?
+            .ok_or("2d context is unavailable")?
+            .dyn_into::<web_sys::CanvasRenderingContext2d>()?;
+
+        // JPEG は透過を持たないので、image クレートと同じく不透明度を無視して RGB をそのまま使う
+        let mut data = rgba.as_raw().clone();
+        for pixel in data.chunks_exact_mut(4) {
+            pixel[3] = 255;
+        }
+        let image_data = web_sys::ImageData::new_with_u8_clamped_array_and_sh(
+            Clamped(&data),
+            rgba.width(),
+            rgba.height(),
+        )?;
+        context.put_image_data(&image_data, 0.0, 0.0)?;
+
+        let quality = JsValue::from_f64(quality);
+        let blob = js_sys::Promise::new(&mut |resolve, reject| {
+            if let Err(e) =
+                canvas.to_blob_with_type_and_encoder_options(&resolve, mime_type, &quality)
+            {
+                let _ = reject.call1(&JsValue::NULL, &e);
+            }
+        });
+        let pending = PendingEncode::default();
+        let result = pending.clone();
+        let mime_type = mime_type.to_string();
+        wasm_bindgen_futures::spawn_local(async move {
+            let encoded = Self::read_blob(blob)
+                .await
+                .map_err(|e| format!("{} encoding failed: {}", mime_type, js_error_reason(&e)));
+            *result.0.borrow_mut() = Some(encoded);
+        });
+        Ok(pending)
+    }
+
+    async fn read_blob(blob: js_sys::Promise) -> Result<Vec<u8>, JsValue> {
+        // 圧縮に失敗した場合（ブラウザの上限を超える大きさなど）は null が渡される
+        let blob = JsFuture::from(blob)
+            .await?
+            .dyn_into::<web_sys::Blob>()
+            .map_err(|_| JsValue::from_str("canvas.toBlob returned no image"))?;
+        let buffer = JsFuture::from(blob.array_buffer()).await?;
+        Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+    }
+}
@@ -0,0 +1,487 @@
+use crate::canvas_items::{CanvasItem, ItemMetadata, StrokeRect};
+use crate::document::Document;
+use crate::state::ToolStyle;
+use quick_xml::Writer;
+use quick_xml::events::{BytesText, Event};
+use serde::{Deserialize, Serialize};
+use std::io;
+
+/// COCO JSON を一括出力するときのファイル名
+pub const COCO_FILENAME: &str = "annotations_coco.json";
+
+/// クラス名が未設定のアイテムに使うクラス名
+const DEFAULT_CLASS_NAME: &str = "object";
+
+#[derive(Serialize, Deserialize)]
+struct CocoFile {
+    images: Vec<CocoImage>,
+    annotations: Vec<CocoAnnotation>,
+    categories: Vec<CocoCategory>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CocoImage {
+    id: u64,
+    file_name: String,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CocoAnnotation {
+    id: u64,
+    image_id: u64,
+    category_id: u64,
+    /// 左上の x, y と幅, 高さ
+    bbox: [f32; 4],
+    #[serde(default)]
+    area: f32,
+    #[serde(default)]
+    iscrowd: u8,
+    #[serde(default)]
+    attributes: CocoAttributes,
+}
+
+/// Annoto 独自の付加情報（他のツールでは無視される）
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CocoAttributes {
+    note: String,
+    tags: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CocoCategory {
+    id: u64,
+    name: String,
+    #[serde(default)]
+    supercategory: String,
+}
+
+/// 読み込んだ1つの矩形
+pub struct DatasetBox {
+    /// 画像座標での範囲
+    pub rect: egui::Rect,
+    pub metadata: ItemMetadata,
+}
+
+impl DatasetBox {
+    /// 枠線の矩形としてアイテムを作る
+    pub fn to_item(&self, style: &ToolStyle) -> CanvasItem {
+        CanvasItem::StrokeRect(StrokeRect {
+            x1: self.rect.min.x,
+            y1: self.rect.min.y,
+            x2: self.rect.max.x,
+            y2: self.rect.max.y,
+            stroke_width: style.stroke_width,
+            stroke_color: style.stroke_color,
+            opacity: style.opacity,
+            rounding: style.rounding,
+            metadata: self.metadata.clone(),
+        })
+    }
+}
+
+/// 読み込んだ1枚分のアノテーション
+pub struct DatasetImage {
+    /// 対象の画像のファイル名（記載がなければ None）
+    pub file_name: Option<String>,
+    pub boxes: Vec<DatasetBox>,
+}
+
+/// 機械学習向けのアノテーション形式（COCO / Pascal VOC）との変換
+pub struct DatasetFormat;
+
+impl DatasetFormat {
+    /// 全ドキュメントの矩形を COCO JSON にする
+    pub fn to_coco(documents: &[Document]) -> Result<String, String> {
+        Self::write_coco(documents.iter().map(|document| {
+            (
+                document.name(),
+                document.image_size(),
+                document.rectangles.as_slice(),
+            )
+        }))
+    }
+
+    /// ドキュメントの矩形を Pascal VOC XML にする
+    pub fn to_pascal_voc(document: &Document) -> Result<String, String> {
+        Self::write_pascal_voc(document.name(), document.image_size(), &document.rectangles)
+            .map_err(|e| e.to_string())
+    }
+
+    /// 画像ごとのファイル名・大きさ・アイテムから COCO JSON を作る
+    fn write_coco<'a>(
+        images: impl Iterator<Item = (&'a str, (u32, u32), &'a [CanvasItem])>,
+    ) -> Result<String, String> {
+        let mut file = CocoFile {
+            images: Vec::new(),
+            annotations: Vec::new(),
+            categories: Vec::new(),
+        };
+        for (image_index, (name, (width, height), items)) in images.enumerate() {
+            let image_id = image_index as u64 + 1;
+            file.images.push(CocoImage {
+                id: image_id,
+                file_name: name.to_string(),
+                width,
+                height,
+            });
+            for (label, rect, metadata) in Self::boxes(items) {
+                let category_id = match file.categories.iter().find(|c| c.name == label) {
+                    Some(category) => category.id,
+                    None => {
+                        let id = file.categories.len() as u64 + 1;
+                        file.categories.push(CocoCategory {
+                            id,
+                            name: label,
+                            supercategory: String::new(),
+                        });
+                        id
+                    }
+                };
+                file.annotations.push(CocoAnnotation {
+                    id: file.annotations.len() as u64 + 1,
+                    image_id,
+                    category_id,
+                    bbox: [rect.min.x, rect.min.y, rect.width(), rect.height()],
+                    area: rect.area(),
+                    iscrowd: 0,
+                    attributes: CocoAttributes {
+                        note: metadata.note.clone(),
+                        tags: metadata.tags.clone(),
+                    },
+                });
+            }
+        }
+        serde_json::to_string_pretty(&file).map_err(|e| e.to_string())
+    }
+
+    /// 1枚の画像のファイル名・大きさ・アイテムから Pascal VOC XML を作る
+    fn write_pascal_voc(
+        name: &str,
+        (width, height): (u32, u32),
+        items: &[CanvasItem],
+    ) -> io::Result<String> {
+        let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+        writer
+            .create_element("annotation")
+            .write_inner_content(|writer| {
+                Self::write_text(writer, "filename", name)?;
+                writer
+                    .create_element("size")
+                    .write_inner_content(|writer| {
+                        Self::write_text(writer, "width", &width.to_string())?;
+                        Self::write_text(writer, "height", &height.to_string())?;
+                        Self::write_text(writer, "depth", "3")
+                    })?;
+                Self::write_text(writer, "segmented", "0")?;
+                for (label, rect, metadata) in Self::boxes(items) {
+                    writer
+                        .create_element("object")
+                        .write_inner_content(|writer| {
+                            Self::write_text(writer, "name", &label)?;
+                            Self::write_text(writer, "pose", "Unspecified")?;
+                            Self::write_text(writer, "truncated", "0")?;
+                            Self::write_text(writer, "difficult", "0")?;
+                            if !metadata.note.is_empty() {
+                                Self::write_text(writer, "note", &metadata.note)?;
+                            }
+                            for tag in &metadata.tags {
+                                Self::write_text(writer, "tag", tag)?;
+                            }
+                            // VOC の座標は 1 始まりで、右下のピクセルを含む
+                            writer
+                                .create_element("bndbox")
+                                .write_inner_content(|writer| {
+                                    let bounds = [
+                                        ("xmin", rect.min.x.floor() as i64 + 1),
+                                        ("ymin", rect.min.y.floor() as i64 + 1),
+                                        ("xmax", rect.max.x.ceil() as i64),
+                                        ("ymax", rect.max.y.ceil() as i64),
+                                    ];
+                                    for (tag, value) in bounds {
+                                        Self::write_text(writer, tag, &value.to_string())?;
+                                    }
+                                    Ok(())
+                                })?;
+                            Ok(())
+                        })?;
+                }
+                Ok(())
+            })?;
+        String::from_utf8(writer.into_inner()).map_err(io::Error::other)
+    }
+
+    /// テキストだけを持つ要素を書き込む（特殊文字はエスケープされる）
+    fn write_text(writer: &mut Writer<Vec<u8>>, tag: &str, text: &str) -> io::Result<()> {
+        writer
+            .create_element(tag)
+            .write_text_content(BytesText::new(text))?;
+        Ok(())
+    }
+
+    /// Pascal VOC のファイル名（元画像の拡張子を .xml にしたもの）
+    pub fn pascal_voc_filename(source_name: &str) -> String {
+        let stem = std::path::Path::new(source_name)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or("image");
+        format!("{}.xml", stem.replace(['/', '\\'], "_"))
+    }
+
+    /// 先頭の文字から COCO JSON か Pascal VOC XML かを判別して読み込む
+    pub fn parse(text: &str) -> Result<Vec<DatasetImage>, String> {
+        match text.trim_start().chars().next() {
+            Some('{') => Self::parse_coco(text),
+            Some('<') => Self::parse_pascal_voc(text).map(|image| vec![image]),
+            _ => Err("COCO JSON または Pascal VOC XML ではありません".to_string()),
+        }
+    }
+
+    fn parse_coco(text: &str) -> Result<Vec<DatasetImage>, String> {
+        let file: CocoFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        let images = file
+            .images
+            .iter()
+            .map(|image| DatasetImage {
+                file_name: Some(image.file_name.clone()),
+                boxes: file
+                    .annotations
+                    .iter()
+                    .filter(|annotation| annotation.image_id == image.id)
+                    .map(|annotation| {
+                        let [x, y, width, height] = annotation.bbox;
+                        let label = file
+                            .categories
+                            .iter()
+                            .find(|category| category.id == annotation.category_id)
+                            .map(|category| category.name.trim().to_string())
+                            .unwrap_or_default();
+                        DatasetBox {
+                            rect: egui::Rect::from_min_size(
+                                egui::pos2(x, y),
+                                egui::vec2(width, height),
+                            ),
+                            metadata: ItemMetadata {
+                                label,
+                                note: annotation.attributes.note.clone(),
+                                tags: annotation.attributes.tags.clone(),
+                            },
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(images)
+    }
+
+    fn parse_pascal_voc(text: &str) -> Result<DatasetImage, String> {
+        let mut reader = quick_xml::Reader::from_str(text);
+        reader.config_mut().trim_text(true);
+        // 開いているタグの名前
+        let mut path: Vec<String> = Vec::new();
+        let mut image = DatasetImage {
+            file_name: None,
+            boxes: Vec::new(),
+        };
+        let mut metadata = ItemMetadata::default();
+        let mut bounds = [None; 4];
+        loop {
+            match reader.read_event().map_err(|e| e.to_string())? {
+                Event::Start(tag) => {
+                    let name = String::from_utf8_lossy(tag.name().as_ref()).into_owned();
+                    if name == "object" {
+                        metadata = ItemMetadata::default();
+                        bounds = [None; 4];
+                    }
+                    path.push(name);
+                }
+                Event::End(_) => {
+                    if path.pop().as_deref() != Some("object") {
+                        continue;
+                    }
+                    let [Some(xmin), Some(ymin), Some(xmax), Some(ymax)] = bounds else {
+                        return Err("bndbox の座標が不足しています".to_string());
+                    };
+                    metadata.label = metadata.label.trim().to_string();
+                    image.boxes.push(DatasetBox {
+                        rect: egui::Rect::from_min_max(
+                            egui::pos2(xmin - 1.0, ymin - 1.0),
+                            egui::pos2(xmax, ymax),
+                        ),
+                        metadata: std::mem::take(&mut metadata),
+                    });
+                }
+                Event::Text(text) => {
+                    let value = text.unescape().map_err(|e| e.to_string())?;
+                    let path: Vec<&str> = path.iter().map(String::as_str).collect();
+                    match path.as_slice() {
+                        ["annotation", "filename"] => image.file_name = Some(value.into_owned()),
+                        ["annotation", "object", "name"] => metadata.label = value.into_owned(),
+                        ["annotation", "object", "note"] => metadata.note = value.into_owned(),
+                        ["annotation", "object", "tag"] => metadata.tags.push(value.into_owned()),
+                        ["annotation", "object", "bndbox", coordinate] => {
+                            let index = ["xmin", "ymin", "xmax", "ymax"]
+                                .iter()
+                                .position(|name| name == coordinate);
+                            if let Some(index) = index {
+                                bounds[index] =
+                                    Some(value.trim().parse::<f32>().map_err(|_| {
+                                        format!(
+                                            "{} の値が数値ではありません: {}",
+                                            coordinate, value
+                                        )
+                                    })?);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(image)
+    }
+
+    /// 出力する矩形とクラス名（未設定なら既定のクラス名）
+    fn boxes(items: &[CanvasItem]) -> Vec<(String, egui::Rect, &ItemMetadata)> {
+        items
+            .iter()
+            .filter_map(|item| {
+                let rect = item.bounding_box()?;
+                let metadata = item.metadata();
+                let label = metadata.label.trim();
+                let label = if label.is_empty() {
+                    DEFAULT_CLASS_NAME
+                } else {
+                    label
+                };
+                Some((label.to_string(), rect, metadata))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dataset_box(rect: egui::Rect, label: &str, note: &str, tags: &[&str]) -> DatasetBox {
+        DatasetBox {
+            rect,
+            metadata: ItemMetadata {
+                label: label.to_string(),
+                note: note.to_string(),
+                tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            },
+        }
+    }
+
+    /// クラス名なし・"object" というクラス名・特殊文字を含むクラス名とメモ・タグ付きの矩形
+    fn sample_boxes() -> Vec<DatasetBox> {
+        vec![
+            dataset_box(
+                egui::Rect::from_min_max(egui::pos2(10.0, 20.0), egui::pos2(50.0, 60.0)),
+                "",
+                "",
+                &[],
+            ),
+            dataset_box(
+                egui::Rect::from_min_max(egui::pos2(30.0, 10.0), egui::pos2(90.0, 40.0)),
+                "object",
+                "",
+                &[],
+            ),
+            dataset_box(
+                egui::Rect::from_min_max(egui::pos2(0.0, 5.0), egui::pos2(120.0, 80.0)),
+                "car & <truck>",
+                "左側 \"奥\"",
+                &["a", "b"],
+            ),
+        ]
+    }
+
+    /// 読み込み後の矩形（クラス名なしは既定のクラス名になる）
+    fn imported(mut boxes: Vec<DatasetBox>) -> Vec<DatasetBox> {
+        for dataset_box in &mut boxes {
+            if dataset_box.metadata.label.is_empty() {
+                dataset_box.metadata.label = DEFAULT_CLASS_NAME.to_string();
+            }
+        }
+        boxes
+    }
+
+    fn items(boxes: &[DatasetBox]) -> Vec<CanvasItem> {
+        let style = ToolStyle::default();
+        boxes
+            .iter()
+            .map(|dataset_box| dataset_box.to_item(&style))
+            .collect()
+    }
+
+    fn assert_boxes_eq(actual: &[DatasetBox], expected: &[DatasetBox]) {
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert_eq!(actual.rect, expected.rect);
+            assert!(actual.metadata == expected.metadata);
+        }
+    }
+
+    #[test]
+    fn coco_round_trip() {
+        let boxes = sample_boxes();
+        let items = items(&boxes);
+        let json = DatasetFormat::write_coco(
+            [
+                ("a.png", (200, 100), items.as_slice()),
+                ("b.png", (10, 10), &[][..]),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+        // クラス名のない矩形は既定のクラス名で出力する
+        assert!(json.contains("\"name\": \"object\""));
+
+        let images = DatasetFormat::parse(&json).unwrap();
+        assert_eq!(images.len(), 2);
+        assert_eq!(images[0].file_name.as_deref(), Some("a.png"));
+        assert_boxes_eq(&images[0].boxes, &imported(boxes));
+        assert!(images[1].boxes.is_empty());
+    }
+
+    #[test]
+    fn pascal_voc_round_trip() {
+        let boxes = sample_boxes();
+        let xml = DatasetFormat::write_pascal_voc("a&b.png", (200, 100), &items(&boxes)).unwrap();
+        assert!(xml.contains("<name>object</name>"));
+        // 1 始まりで右下のピクセルを含む座標
+        assert!(xml.contains("<xmin>11</xmin>"));
+        assert!(xml.contains("<ymin>21</ymin>"));
+        assert!(xml.contains("<xmax>50</xmax>"));
+        assert!(xml.contains("<ymax>60</ymax>"));
+
+        let image = DatasetFormat::parse(&xml).unwrap().remove(0);
+        assert_eq!(image.file_name.as_deref(), Some("a&b.png"));
+        assert_boxes_eq(&image.boxes, &imported(boxes));
+    }
+
+    #[test]
+    fn pascal_voc_rounds_out_fractional_boxes() {
+        let boxes = [dataset_box(
+            egui::Rect::from_min_max(egui::pos2(10.4, 20.6), egui::pos2(49.2, 59.9)),
+            "dog",
+            "",
+            &[],
+        )];
+        let xml = DatasetFormat::write_pascal_voc("a.png", (200, 100), &items(&boxes)).unwrap();
+        let image = DatasetFormat::parse(&xml).unwrap().remove(0);
+        // 矩形を含むピクセルの範囲に広げる
+        assert_eq!(
+            image.boxes[0].rect,
+            egui::Rect::from_min_max(egui::pos2(10.0, 20.0), egui::pos2(50.0, 60.0))
+        );
+    }
+}
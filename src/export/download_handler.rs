@@ -1,3 +1,5 @@
+use crate::error::{AppError, js_error_reason};
+use crate::export::ImageExporter;
 use js_sys;
 use wasm_bindgen::prelude::*;
 use web_sys;
@@ -6,28 +8,49 @@ pub struct DownloadHandler;
 
 impl DownloadHandler {
     /// ブラウザでファイルをダウンロード
-    pub fn download_image(data: &[u8], format: &str) {
-        web_sys::console::log_1(&"Creating blob".into());
+    pub fn download_image(data: &[u8], format: &str, filename: &str) -> Result<(), AppError> {
+        Self::download_file(data, ImageExporter::mime_type(format), filename)
+    }
+
+    /// 複数画像をまとめた ZIP をダウンロード
+    pub fn download_zip(data: &[u8], filename: &str) -> Result<(), AppError> {
+        Self::download_file(data, "application/zip", filename)
+    }
+
+    /// JSON ファイルをダウンロード
+    pub fn download_json(text: &str, filename: &str) -> Result<(), AppError> {
+        Self::download_file(text.as_bytes(), "application/json", filename)
+    }
+
+    /// XML ファイルをダウンロード
+    pub fn download_xml(text: &str, filename: &str) -> Result<(), AppError> {
+        Self::download_file(text.as_bytes(), "application/xml", filename)
+    }
+
+    fn download_file(data: &[u8], mime_type: &str, filename: &str) -> Result<(), AppError> {
+        Self::start_download(data, mime_type, filename).map_err(|e| AppError::Download {
+            name: filename.to_string(),
+            reason: js_error_reason(&e),
+        })
+    }
+
+    fn start_download(data: &[u8], mime_type: &str, filename: &str) -> Result<(), JsValue> {
         let bag = web_sys::BlobPropertyBag::new();
-        bag.set_type(&format!("image/{}", format));
+        bag.set_type(mime_type);
         let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(
             &js_sys::Array::of1(&js_sys::Uint8Array::from(data)),
             &bag,
-        )
-        .unwrap();
-        let url = web_sys::Url::create_object_url_with_blob(&blob).unwrap();
-        web_sys::console::log_1(&format!("Blob URL: {}", url).into());
-        let document = web_sys::window().unwrap().document().unwrap();
+        )?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob)?;
+        let document = web_sys::window()
+            .and_then(|window| window.document())
+            .ok_or("document is unavailable")?;
         let a = document
-            .create_element("a")
-            .unwrap()
-            .dyn_into::<web_sys::HtmlElement>()
-            .unwrap();
-        a.set_attribute("href", &url).unwrap();
-        a.set_attribute("download", &format!("exported.{}", format))
-            .unwrap();
+            .create_element("a")?
+            .dyn_into::<web_sys::HtmlElement>()?;
+        a.set_attribute("href", &url)?;
+        a.set_attribute("download", filename)?;
         a.click();
-        web_sys::Url::revoke_object_url(&url).unwrap();
-        web_sys::console::log_1(&"Download initiated".into());
+        web_sys::Url::revoke_object_url(&url)
     }
 }
@@ -0,0 +1,670 @@
+use crate::canvas_items::CanvasItem;
+use crate::document::{DecodedImage, Document};
+use crate::export::ImageExporter;
+use crate::export::canvas_encoder::{CanvasEncoder, PendingEncode};
+use crate::state::{ExportResize, UiState};
+use image::RgbaImage;
+use image::imageops::{self, FilterType};
+use std::cell::RefCell;
+use std::io::Write;
+use std::rc::Rc;
+use std::sync::Arc;
+
+/// 1フレームでエクスポート処理に使う時間（ミリ秒）
+const FRAME_BUDGET_MS: f64 = 12.0;
+/// リサイズを区切る単位（行数・列数）
+const RESIZE_CHUNK: u32 = 32;
+/// シャープ処理・乗算・PNG エンコードを区切る行数
+const ROW_CHUNK: u32 = 64;
+/// アンシャープマスクをかけない明るさの差（ノイズの強調を抑える）
+const SHARPEN_THRESHOLD: i32 = 2;
+
+/// ダイアログに表示する進捗
+pub struct ExportProgress {
+    pub fraction: f32,
+    pub label: String,
+}
+
+/// PNG エンコーダーの出力先（エンコーダーが所有するため、結果は共有して受け取る）
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// エクスポート処理の段階
+enum Stage {
+    Start,
+    /// 横方向の縮尺変更（元画像の行ごと）
+    ResizeHorizontal {
+        done: u32,
+        output: RgbaImage,
+    },
+    /// 縦方向の縮尺変更（横方向の結果の列ごと）
+    ResizeVertical {
+        done: u32,
+        source: RgbaImage,
+        output: RgbaImage,
+    },
+    /// アンシャープマスク（ぼかしの届く範囲を重ねた行の帯ごと）
+    Sharpen {
+        done: u32,
+        source: RgbaImage,
+        output: RgbaImage,
+    },
+    /// 描画用に乗算済みアルファへ変換（行の帯ごと）
+    Premultiply {
+        done: u32,
+        rgba: RgbaImage,
+        data: Vec<u8>,
+    },
+    /// 出力サイズに縮尺を変えたアイテムを描画（リサイズでぼやけないよう最後に描く）。1アイテムずつ
+    Draw {
+        done: usize,
+        rgba: RgbaImage,
+        pixmap: tiny_skia::Pixmap,
+        /// 描画前の乗算済みピクセル
+        before: Vec<u8>,
+    },
+    /// 描画で変わったピクセルを乗算前の値に戻す（行の帯ごと）
+    Demultiply {
+        done: u32,
+        rgba: RgbaImage,
+        pixmap: tiny_skia::Pixmap,
+        before: Vec<u8>,
+    },
+    Encode(RgbaImage),
+    /// PNG の行ごとの書き込み
+    EncodePng {
+        done: u32,
+        rgba: RgbaImage,
+        writer: Box<png::StreamWriter<'static, SharedBuffer>>,
+        buffer: SharedBuffer,
+    },
+    /// ブラウザでの JPEG 圧縮の完了待ち（失敗したときのために画素も持っておく）
+    EncodeJpeg {
+        rgba: RgbaImage,
+        pending: PendingEncode,
+    },
+    Finished,
+}
+
+/// 1枚の画像のエクスポート。UI を止めないよう、毎フレーム少しずつ進める
+pub struct ImageExportJob {
+    image: Arc<DecodedImage>,
+    items: Vec<CanvasItem>,
+    format: String,
+    jpeg_quality: u8,
+    width: u32,
+    height: u32,
+    scale_factor: egui::Vec2,
+    filter: FilterType,
+    /// アンシャープマスクのぼかし半径（かけない場合は None）
+    sharpen_sigma: Option<f32>,
+    /// 画像を含めずアイテムだけを描く
+    overlay_only: bool,
+    stage: Stage,
+    /// ブラウザでの JPEG 圧縮に失敗し、アプリ内での圧縮に切り替えた理由
+    fallback_reason: Option<String>,
+}
+
+impl ImageExportJob {
+    pub fn new(
+        image: Arc<DecodedImage>,
+        items: Vec<CanvasItem>,
+        format: &str,
+        jpeg_quality: u8,
+        resize: &ExportResize,
+    ) -> Self {
+        let (width, height) = ImageExporter::output_size(image.width(), image.height(), resize);
+        // 縦横比を保たないリサイズでは縦と横で倍率が異なる
+        let scale_factor = egui::vec2(
+            width as f32 / image.width() as f32,
+            height as f32 / image.height() as f32,
+        );
+        Self {
+            image,
+            items,
+            format: format.to_string(),
+            jpeg_quality,
+            width,
+            height,
+            scale_factor,
+            filter: resize.filter.filter_type(),
+            sharpen_sigma: resize.sharpen.then_some(resize.sharpen_sigma),
+            overlay_only: false,
+            stage: Stage::Start,
+            fallback_reason: None,
+        }
+    }
+
+    /// エクスポートダイアログの設定でドキュメントを出力する
+    pub fn for_document(document: &Document, ui_state: &UiState) -> Self {
+        let mut job = Self::new(
+            document.image.clone(),
+            document.rectangles.clone(),
+            ImageExporter::output_format(ui_state),
+            ui_state.export_jpeg_quality,
+            &ui_state.export_resize,
+        );
+        if ui_state.export_overlay_only {
+            // 元画像と同じ大きさの透明なキャンバスに描く
+            job.width = document.image.width();
+            job.height = document.image.height();
+            job.scale_factor = egui::Vec2::splat(1.0);
+            job.overlay_only = true;
+        }
+        job
+    }
+
+    /// プレビュー用に、縮小した元画像（source）から size の大きさで出力する
+    pub fn for_preview(
+        document: &Document,
+        ui_state: &UiState,
+        source: Arc<DecodedImage>,
+        size: (u32, u32),
+    ) -> Self {
+        // アイテムは元画像の座標なので、縮小した元画像の座標に合わせる
+        let source_scale = egui::vec2(
+            source.width() as f32 / document.image.width() as f32,
+            source.height() as f32 / document.image.height() as f32,
+        );
+        let items = document
+            .rectangles
+            .iter()
+            .map(|item| item.scale(source_scale))
+            .collect();
+        let (width, height) = if ui_state.export_overlay_only {
+            (source.width(), source.height())
+        } else {
+            size
+        };
+        let mut job = Self::new(
+            source,
+            items,
+            ImageExporter::output_format(ui_state),
+            ui_state.export_jpeg_quality,
+            &ui_state.export_resize,
+        );
+        job.scale_factor = egui::vec2(
+            width as f32 / job.image.width() as f32,
+            height as f32 / job.image.height() as f32,
+        );
+        job.width = width;
+        job.height = height;
+        job.overlay_only = ui_state.export_overlay_only;
+        job
+    }
+
+    /// 時間の許す範囲で処理を進め、完了したら結果を返す
+    pub fn step(&mut self) -> Option<Result<Vec<u8>, String>> {
+        let started = js_sys::Date::now();
+        loop {
+            if let Some(result) = self.advance() {
+                return Some(result);
+            }
+            // ブラウザでの圧縮中は次のフレームで結果を確認する
+            if matches!(self.stage, Stage::EncodeJpeg { .. })
+                || js_sys::Date::now() - started >= FRAME_BUDGET_MS
+            {
+                return None;
+            }
+        }
+    }
+
+    /// 0.0〜1.0 の進捗率
+    pub fn progress(&self) -> f32 {
+        let height = self.height.max(1) as f32;
+        match &self.stage {
+            Stage::Start => 0.0,
+            Stage::ResizeHorizontal { done, .. } => {
+                0.3 * *done as f32 / self.image.height().max(1) as f32
+            }
+            Stage::ResizeVertical { done, .. } => {
+                0.3 + 0.3 * *done as f32 / self.width.max(1) as f32
+            }
+            Stage::Sharpen { done, .. } => 0.6 + 0.1 * *done as f32 / height,
+            Stage::Premultiply { done, .. } => 0.7 + 0.05 * *done as f32 / height,
+            Stage::Draw { done, .. } => 0.75 + 0.05 * *done as f32 / self.items.len().max(1) as f32,
+            Stage::Demultiply { done, .. } => 0.8 + 0.05 * *done as f32 / height,
+            Stage::Encode(_) => 0.85,
+            Stage::EncodePng { done, .. } => 0.85 + 0.15 * *done as f32 / height,
+            Stage::EncodeJpeg { .. } => 0.9,
+            Stage::Finished => 1.0,
+        }
+    }
+
+    pub fn stage_label(&self) -> &'static str {
+        match self.stage {
+            Stage::Start | Stage::ResizeHorizontal { .. } | Stage::ResizeVertical { .. } => {
+                "リサイズ中"
+            }
+            Stage::Sharpen { .. } => "シャープ処理中",
+            Stage::Premultiply { .. } | Stage::Draw { .. } | Stage::Demultiply { .. } => "描画中",
+            Stage::Encode(_) | Stage::EncodePng { .. } | Stage::EncodeJpeg { .. } => "エンコード中",
+            Stage::Finished => "完了",
+        }
+    }
+
+    /// 処理を1単位進める
+    fn advance(&mut self) -> Option<Result<Vec<u8>, String>> {
+        let source = &self.image.pixels;
+        let (source_width, source_height) = source.dimensions();
+        self.stage = match std::mem::replace(&mut self.stage, Stage::Finished) {
+            Stage::Start => {
+                if self.overlay_only {
+                    self.start_drawing(RgbaImage::new(self.width, self.height))
+                } else if (self.width, self.height) == (source_width, source_height) {
+                    // 元の画素は描画で書き換えるため複製する
+                    self.start_drawing(source.clone())
+                } else {
+                    Stage::ResizeHorizontal {
+                        done: 0,
+                        output: RgbaImage::new(self.width, source_height),
+                    }
+                }
+            }
+            Stage::ResizeHorizontal { done, mut output } => {
+                // 高さを変えない縮尺変更は縦方向に影響しないので、行の帯ごとに処理できる
+                let rows = RESIZE_CHUNK.min(source_height - done);
+                let band = imageops::crop_imm(source, 0, done, source_width, rows);
+                let resized = imageops::resize(&*band, self.width, rows, self.filter);
+                imageops::replace(&mut output, &resized, 0, done as i64);
+                if done + rows < source_height {
+                    Stage::ResizeHorizontal {
+                        done: done + rows,
+                        output,
+                    }
+                } else {
+                    Stage::ResizeVertical {
+                        done: 0,
+                        output: RgbaImage::new(self.width, self.height),
+                        source: output,
+                    }
+                }
+            }
+            Stage::ResizeVertical {
+                done,
+                source,
+                mut output,
+            } => {
+                let columns = RESIZE_CHUNK.min(self.width - done);
+                let band = imageops::crop_imm(&source, done, 0, columns, source_height);
+                let resized = imageops::resize(&*band, columns, self.height, self.filter);
+                imageops::replace(&mut output, &resized, done as i64, 0);
+                if done + columns < self.width {
+                    Stage::ResizeVertical {
+                        done: done + columns,
+                        source,
+                        output,
+                    }
+                } else if self.sharpen_sigma.is_some() {
+                    Stage::Sharpen {
+                        done: 0,
+                        output: RgbaImage::new(self.width, self.height),
+                        source: output,
+                    }
+                } else {
+                    self.start_drawing(output)
+                }
+            }
+            Stage::Sharpen {
+                done,
+                source,
+                mut output,
+            } => {
+                let rows = ROW_CHUNK.min(self.height - done);
+                let band =
+                    Self::sharpen_band(&source, done, rows, self.sharpen_sigma.unwrap_or(1.0));
+                imageops::replace(&mut output, &band, 0, done as i64);
+                if done + rows < self.height {
+                    Stage::Sharpen {
+                        done: done + rows,
+                        source,
+                        output,
+                    }
+                } else {
+                    self.start_drawing(output)
+                }
+            }
+            Stage::Premultiply {
+                done,
+                rgba,
+                mut data,
+            } => {
+                let rows = ROW_CHUNK.min(self.height - done);
+                let row_bytes = self.width as usize * 4;
+                let band = done as usize * row_bytes..(done + rows) as usize * row_bytes;
+                ImageExporter::premultiply(&mut data[band]);
+                if done + rows < self.height {
+                    Stage::Premultiply {
+                        done: done + rows,
+                        rgba,
+                        data,
+                    }
+                } else {
+                    let before = data.clone();
+                    let size = tiny_skia::IntSize::from_wh(self.width, self.height);
+                    let Some(pixmap) =
+                        size.and_then(|size| tiny_skia::Pixmap::from_vec(data, size))
+                    else {
+                        return Some(Err("Failed to create pixmap".to_string()));
+                    };
+                    Stage::Draw {
+                        done: 0,
+                        rgba,
+                        pixmap,
+                        before,
+                    }
+                }
+            }
+            Stage::Draw {
+                done,
+                rgba,
+                mut pixmap,
+                before,
+            } => {
+                // モザイクはそれまでに描いたアイテムを含めて処理するので、順に1つずつ描く
+                let item = &self.items[done..done + 1];
+                if self.overlay_only {
+                    ImageExporter::draw_overlay_items(&mut pixmap, item, source);
+                } else {
+                    ImageExporter::draw_items(&mut pixmap, item, self.scale_factor);
+                }
+                if done + 1 < self.items.len() {
+                    Stage::Draw {
+                        done: done + 1,
+                        rgba,
+                        pixmap,
+                        before,
+                    }
+                } else {
+                    Stage::Demultiply {
+                        done: 0,
+                        rgba,
+                        pixmap,
+                        before,
+                    }
+                }
+            }
+            Stage::Demultiply {
+                done,
+                mut rgba,
+                pixmap,
+                before,
+            } => {
+                // 描画で変化しなかったピクセルは元の値を保つ
+                let rows = ROW_CHUNK.min(self.height - done);
+                let row_bytes = self.width as usize * 4;
+                let band = done as usize * row_bytes..(done + rows) as usize * row_bytes;
+                ImageExporter::demultiply_changed(
+                    &mut (*rgba)[band.clone()],
+                    &pixmap.data()[band.clone()],
+                    &before[band],
+                );
+                if done + rows < self.height {
+                    Stage::Demultiply {
+                        done: done + rows,
+                        rgba,
+                        pixmap,
+                        before,
+                    }
+                } else {
+                    Stage::Encode(rgba)
+                }
+            }
+            Stage::Encode(rgba) => match self.format.as_str() {
+                "PNG" => match Self::png_writer(&rgba) {
+                    Ok((writer, buffer)) => Stage::EncodePng {
+                        done: 0,
+                        rgba,
+                        writer,
+                        buffer,
+                    },
+                    Err(e) => return Some(Err(e)),
+                },
+                // JPEG の圧縮は分割できないので、ブラウザに任せて完了を待つ
+                "JPEG" => match CanvasEncoder::start_jpeg(&rgba, self.jpeg_quality) {
+                    Ok(pending) => Stage::EncodeJpeg { rgba, pending },
+                    Err(reason) => return Some(self.encode_fallback(&rgba, reason)),
+                },
+                _ => {
+                    return Some(ImageExporter::encode(
+                        &rgba,
+                        &self.format,
+                        self.jpeg_quality,
+                    ));
+                }
+            },
+            Stage::EncodePng {
+                done,
+                rgba,
+                mut writer,
+                buffer,
+            } => {
+                let rows = ROW_CHUNK.min(self.height - done);
+                let row_bytes = self.width as usize * 4;
+                let band = done as usize * row_bytes..(done + rows) as usize * row_bytes;
+                if let Err(e) = writer.write_all(&(*rgba)[band]) {
+                    return Some(Err(format!("PNG encoding failed: {}", e)));
+                }
+                if done + rows < self.height {
+                    Stage::EncodePng {
+                        done: done + rows,
+                        rgba,
+                        writer,
+                        buffer,
+                    }
+                } else {
+                    if let Err(e) = writer.finish() {
+                        return Some(Err(format!("PNG encoding failed: {}", e)));
+                    }
+                    return Some(Ok(buffer.0.take()));
+                }
+            }
+            Stage::EncodeJpeg { rgba, pending } => match pending.take() {
+                Some(Ok(data)) => return Some(Ok(data)),
+                Some(Err(reason)) => return Some(self.encode_fallback(&rgba, reason)),
+                None => Stage::EncodeJpeg { rgba, pending },
+            },
+            Stage::Finished => return Some(Err("Export already finished".to_string())),
+        };
+        None
+    }
+
+    /// ブラウザで圧縮できなかった画像をアプリ内で圧縮する（UI は圧縮が終わるまで止まる）
+    fn encode_fallback(&mut self, rgba: &RgbaImage, reason: String) -> Result<Vec<u8>, String> {
+        self.fallback_reason = Some(reason);
+        ImageExporter::encode(rgba, &self.format, self.jpeg_quality)
+    }
+
+    /// ブラウザでの圧縮に失敗してアプリ内で圧縮した場合、その理由を取り出す（通知用）
+    pub fn take_fallback_reason(&mut self) -> Option<String> {
+        self.fallback_reason.take()
+    }
+
+    /// アイテムを描く段階に進む（アイテムがなければそのままエンコードする）
+    fn start_drawing(&self, rgba: RgbaImage) -> Stage {
+        if self.items.is_empty() {
+            return Stage::Encode(rgba);
+        }
+        // 乗算済みの値は元の画素とは別に持つ
+        let data = rgba.as_raw().clone();
+        Stage::Premultiply {
+            done: 0,
+            rgba,
+            data,
+        }
+    }
+
+    /// done 行目から rows 行分にアンシャープマスクをかける。
+    /// ぼかしの範囲（2σ）だけ上下に余分に切り出し、画像全体にかけた場合と同じ結果にする
+    fn sharpen_band(source: &RgbaImage, done: u32, rows: u32, sigma: f32) -> RgbaImage {
+        // image クレートは 0 以下の半径を 1.0 として扱う
+        let margin = (2.0 * sigma.max(1.0)).ceil() as u32 + 1;
+        let top = done.saturating_sub(margin);
+        let bottom = (done + rows + margin).min(source.height());
+        let band = imageops::crop_imm(source, 0, top, source.width(), bottom - top);
+        let sharpened = imageops::unsharpen(&*band, sigma, SHARPEN_THRESHOLD);
+        imageops::crop_imm(&sharpened, 0, done - top, source.width(), rows).to_image()
+    }
+
+    /// image クレートの PNG 出力と同じ設定で、行ごとに書き込めるエンコーダーを作る
+    fn png_writer(
+        rgba: &RgbaImage,
+    ) -> Result<(Box<png::StreamWriter<'static, SharedBuffer>>, SharedBuffer), String> {
+        let buffer = SharedBuffer::default();
+        let mut encoder = png::Encoder::new(buffer.clone(), rgba.width(), rgba.height());
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_compression(png::Compression::Default);
+        encoder.set_filter(png::FilterType::Sub);
+        encoder.set_adaptive_filter(png::AdaptiveFilterType::Adaptive);
+        let writer = encoder
+            .write_header()
+            .and_then(|writer| writer.into_stream_writer())
+            .map_err(|e| format!("PNG encoding failed: {}", e))?;
+        Ok((Box::new(writer), buffer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::canvas_items::StrokeRect;
+    use crate::state::{ExportResizeMode, ResampleFilter};
+
+    fn test_image() -> RgbaImage {
+        RgbaImage::from_fn(90, 150, |x, y| {
+            image::Rgba([
+                (x * 7 % 256) as u8,
+                (y * 3 % 256) as u8,
+                ((x ^ y) % 256) as u8,
+                255,
+            ])
+        })
+    }
+
+    /// Date を使わずに最後まで進める
+    fn run(job: &mut ImageExportJob) -> Vec<u8> {
+        loop {
+            if let Some(result) = job.advance() {
+                return result.unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn banded_resize_matches_whole_image() {
+        // 帯ごとの処理では横→縦の間で u8 に丸めるため、1 段階の差まで許容する。
+        // オーバーシュートするフィルタで途中の値が 0..=255 から外れないよう中間の明るさに収める
+        let mut source = test_image();
+        for value in source.iter_mut() {
+            *value = *value / 4 * 3 + 32;
+        }
+        let resizes = [
+            (ExportResizeMode::Percentage, 41, 0, 0),
+            (ExportResizeMode::Percentage, 230, 0, 0),
+            (ExportResizeMode::Width, 0, 45, 0),
+            (ExportResizeMode::Height, 0, 0, 300),
+        ];
+        for filter in ResampleFilter::ALL {
+            for (mode, percentage, width, height) in resizes {
+                let resize = ExportResize {
+                    mode,
+                    percentage,
+                    width,
+                    height,
+                    keep_aspect: false,
+                    filter,
+                    ..Default::default()
+                };
+                let image = Arc::new(DecodedImage {
+                    pixels: source.clone(),
+                    has_alpha: false,
+                });
+                let mut job = ImageExportJob::new(image, Vec::new(), "PNG", 90, &resize);
+                let output = loop {
+                    job.advance();
+                    if let Stage::Encode(rgba) = &job.stage {
+                        break rgba.clone();
+                    }
+                };
+                let expected =
+                    imageops::resize(&source, job.width, job.height, filter.filter_type());
+                let max_diff = output
+                    .as_raw()
+                    .iter()
+                    .zip(expected.as_raw())
+                    .map(|(a, b)| a.abs_diff(*b))
+                    .max()
+                    .unwrap();
+                assert!(
+                    max_diff <= 1,
+                    "{} {}x{}: max diff {}",
+                    filter.label(),
+                    job.width,
+                    job.height,
+                    max_diff
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn banded_sharpen_matches_whole_image() {
+        let source = test_image();
+        for sigma in [0.5, 1.0, 3.7] {
+            let expected = imageops::unsharpen(&source, sigma, SHARPEN_THRESHOLD);
+            let mut output = RgbaImage::new(source.width(), source.height());
+            let mut done = 0;
+            while done < source.height() {
+                let rows = 17.min(source.height() - done);
+                let band = ImageExportJob::sharpen_band(&source, done, rows, sigma);
+                imageops::replace(&mut output, &band, 0, done as i64);
+                done += rows;
+            }
+            assert_eq!(output, expected, "sigma = {}", sigma);
+        }
+    }
+
+    #[test]
+    fn streamed_png_decodes_to_output() {
+        let source = test_image();
+        let item = CanvasItem::StrokeRect(StrokeRect {
+            x1: 10.0,
+            y1: 20.0,
+            x2: 60.0,
+            y2: 130.0,
+            stroke_width: 3.0,
+            stroke_color: egui::Color32::RED,
+            opacity: 0.5,
+            rounding: 0,
+            metadata: Default::default(),
+        });
+        let image = Arc::new(DecodedImage {
+            pixels: source.clone(),
+            has_alpha: false,
+        });
+        let resize = ExportResize::default();
+        let mut job = ImageExportJob::new(image, vec![item.clone()], "PNG", 90, &resize);
+        let encoded = run(&mut job);
+        let decoded = image::load_from_memory(&encoded).unwrap().into_rgba8();
+
+        // 分割せずに描画した結果と一致する
+        let mut pixmap = ImageExporter::rgba_to_pixmap(&source).unwrap();
+        let before = pixmap.data().to_vec();
+        ImageExporter::draw_items(&mut pixmap, &[item], egui::Vec2::splat(1.0));
+        let expected = ImageExporter::pixmap_to_rgba(&pixmap, source.clone(), &before);
+        assert_ne!(expected, source);
+        assert_eq!(decoded, expected);
+    }
+}
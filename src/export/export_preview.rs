@@ -0,0 +1,189 @@
+use crate::document::{DecodedImage, Document};
+use crate::export::{ImageExportJob, ImageExporter};
+use crate::state::{ExportResize, UiState};
+use crate::tiled_texture::TiledTexture;
+use image::imageops;
+use std::sync::Arc;
+
+/// プレビューの長辺の上限（ピクセル）
+const PREVIEW_MAX_SIDE: u32 = 1024;
+/// 設定の変更が止まってからプレビューを作り直すまでの時間（秒）
+const PREVIEW_DEBOUNCE_SECS: f64 = 0.3;
+
+/// プレビューを作成したときの設定（変わったら作り直す）
+#[derive(Clone, PartialEq)]
+struct PreviewKey {
+    document_id: u64,
+    /// アイテムの変更回数
+    revision: u64,
+    format: String,
+    jpeg_quality: u8,
+    resize: ExportResize,
+    overlay_only: bool,
+}
+
+/// 実際にエンコードした出力
+pub struct PreviewResult {
+    /// エンコード結果をデコードし直したもの（JPEG の劣化も確認できる）
+    pub texture: TiledTexture,
+    /// 実際に出力される大きさ
+    pub size: (u32, u32),
+    /// 出力のファイルサイズ（縮小して作った場合は面積比で見積もったもの）
+    pub encoded_bytes: usize,
+    /// 出力と同じ大きさで作成し、ファイルサイズが正確か
+    pub exact_size: bool,
+}
+
+/// 作成中のプレビュー
+struct PreviewJob {
+    job: ImageExportJob,
+    /// 実際に出力される大きさ
+    output_size: (u32, u32),
+}
+
+/// エクスポートダイアログに表示する出力のプレビュー
+#[derive(Default)]
+pub struct ExportPreview {
+    key: Option<PreviewKey>,
+    /// 最後に変わった設定と、変わった時刻
+    pending_key: Option<(PreviewKey, f64)>,
+    /// 縮小した元画像（ドキュメントの ID ごと）
+    source: Option<(u64, Arc<DecodedImage>)>,
+    job: Option<PreviewJob>,
+    result: Option<PreviewResult>,
+    error: Option<String>,
+}
+
+impl ExportPreview {
+    /// 設定が変わっていれば作り直し、作成中なら処理を進める
+    pub fn update(&mut self, ctx: &egui::Context, document: Option<&Document>, ui_state: &UiState) {
+        let Some(document) = document.filter(|_| ui_state.show_export_dialog) else {
+            // ダイアログを閉じている間はメモリを解放しておく
+            *self = Self::default();
+            return;
+        };
+        let key = PreviewKey {
+            document_id: document.id,
+            revision: document.revision,
+            format: ImageExporter::output_format(ui_state).to_string(),
+            jpeg_quality: ui_state.export_jpeg_quality,
+            resize: ui_state.export_resize,
+            overlay_only: ui_state.export_overlay_only,
+        };
+        if self.key.as_ref() == Some(&key) {
+            // 変更後に元の設定へ戻した
+            self.pending_key = None;
+        } else {
+            let now = ctx.input(|i| i.time);
+            if self.pending_key.as_ref().map(|(pending, _)| pending) != Some(&key) {
+                self.pending_key = Some((key.clone(), now));
+            }
+            let changed_at = self.pending_key.as_ref().map_or(now, |(_, time)| *time);
+            let waited = now - changed_at;
+            // スライダーの操作中などは作り直さず、変更が止まるのを待つ（最初の1枚はすぐに作る）
+            if self.key.is_some() && waited < PREVIEW_DEBOUNCE_SECS {
+                ctx.request_repaint_after(std::time::Duration::from_secs_f64(
+                    PREVIEW_DEBOUNCE_SECS - waited,
+                ));
+            } else {
+                self.start(document, ui_state);
+                self.key = Some(key);
+                self.pending_key = None;
+            }
+        }
+
+        let Some(preview_job) = self.job.as_mut() else {
+            return;
+        };
+        let Some(result) = preview_job.job.step() else {
+            ctx.request_repaint();
+            return;
+        };
+        let output_size = preview_job.output_size;
+        self.job = None;
+        let result = result.and_then(|data| {
+            let rgba = image::load_from_memory(&data)
+                .map_err(|e| e.to_string())?
+                .into_rgba8();
+            // 出力と同じ画質で縮小したものとして、面積に比例させてファイルサイズ を見積もる
+            let (width, height) = rgba.dimensions();
+            let area_ratio =
+                (output_size.0 as f64 * output_size.1 as f64) / (width as f64 * height as f64);
+            Ok(PreviewResult {
+                texture: TiledTexture::new(ctx, "export_preview", &rgba),
+                size: output_size,
+                encoded_bytes: (data.len() as f64 * area_ratio) as usize,
+                exact_size: (width, height) == output_size,
+            })
+        });
+        match result {
+            Ok(result) => {
+                self.result = Some(result);
+                self.error = None;
+            }
+            Err(e) => {
+                self.result = None;
+                self.error = Some(e);
+            }
+        }
+    }
+
+    /// 縮小した元画像からプレビューの作成を始める
+    fn start(&mut self, document: &Document, ui_state: &UiState) {
+        let source = match &self.source {
+            Some((id, source)) if *id == document.id => source.clone(),
+            _ => {
+                let source = Self::downscale(&document.image);
+                self.source = Some((document.id, source.clone()));
+                source
+            }
+        };
+        let output_size = ImageExporter::document_output_size(
+            document.image.width(),
+            document.image.height(),
+            ui_state,
+        );
+        let job = ImageExportJob::for_preview(document, ui_state, source, Self::fit(output_size));
+        self.job = Some(PreviewJob { job, output_size });
+    }
+
+    /// 設定を変えるたびに原寸から処理しないよう、元画像を一度だけ縮小して使い回す
+    fn downscale(image: &Arc<DecodedImage>) -> Arc<DecodedImage> {
+        let (width, height) = Self::fit((image.width(), image.height()));
+        if (width, height) == (image.width(), image.height()) {
+            return image.clone();
+        }
+        Arc::new(DecodedImage {
+            pixels: imageops::thumbnail(&image.pixels, width, height),
+            has_alpha: image.has_alpha,
+        })
+    }
+
+    /// 長辺がプレビューの上限に収まる大きさ
+    fn fit((width, height): (u32, u32)) -> (u32, u32) {
+        let long_edge = width.max(height);
+        if long_edge <= PREVIEW_MAX_SIDE {
+            return (width, height);
+        }
+        let scale = PREVIEW_MAX_SIDE as f64 / long_edge as f64;
+        (
+            ((width as f64 * scale).round() as u32).max(1),
+            ((height as f64 * scale).round() as u32).max(1),
+        )
+    }
+
+    /// 最後に作成したプレビュー（設定の変更後は作り直すまで古いものを返す）
+    pub fn result(&self) -> Option<&PreviewResult> {
+        self.result.as_ref()
+    }
+
+    /// 最後の作成に失敗した理由
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    /// 作成中か、設定の変更が止まるのを待っている
+    pub fn is_rendering(&self) -> bool {
+        self.job.is_some() || self.pending_key.is_some()
+    }
+}
@@ -0,0 +1,65 @@
+use crate::document::Document;
+use crate::export::ImageExporter;
+use crate::state::UiState;
+
+/// エクスポートするファイル名の既定パターン
+pub const DEFAULT_FILENAME_PATTERN: &str = "{name}_annotated.{ext}";
+
+/// ファイル名パターンで使えるトークンの説明
+pub const FILENAME_TOKENS_HELP: &str =
+    "{name}: 元のファイル名, {date}: 日付, {size}: 出力サイズ (幅x高さ), {ext}: 拡張子";
+
+pub struct FileNameTemplate;
+
+impl FileNameTemplate {
+    /// パターン中のトークンを置き換えてファイル名を作る
+    pub fn render(
+        pattern: &str,
+        source_name: &str,
+        format: &str,
+        output_size: (u32, u32),
+        date: &str,
+    ) -> String {
+        let stem = std::path::Path::new(source_name)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .filter(|stem| !stem.is_empty())
+            .unwrap_or("image");
+        let name = pattern
+            .replace("{name}", stem)
+            .replace("{date}", date)
+            .replace("{size}", &format!("{}x{}", output_size.0, output_size.1))
+            .replace("{ext}", ImageExporter::file_extension(format));
+        // ディレクトリとして扱われないようにする
+        let name = name.replace(['/', '\\'], "_");
+        if name.trim().is_empty() {
+            format!("exported.{}", ImageExporter::file_extension(format))
+        } else {
+            name
+        }
+    }
+
+    /// エクスポート設定を反映したドキュメントの出力ファイル名
+    pub fn for_document(document: &Document, ui_state: &UiState, date: &str) -> String {
+        let (width, height) = document.image_size();
+        let output_size = ImageExporter::document_output_size(width, height, ui_state);
+        Self::render(
+            &ui_state.export_filename_pattern,
+            document.name(),
+            ImageExporter::output_format(ui_state),
+            output_size,
+            date,
+        )
+    }
+
+    /// 今日の日付（YYYY-MM-DD）
+    pub fn today() -> String {
+        let now = js_sys::Date::new_0();
+        format!(
+            "{:04}-{:02}-{:02}",
+            now.get_full_year(),
+            now.get_month() + 1,
+            now.get_date()
+        )
+    }
+}
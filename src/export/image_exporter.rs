@@ -1,136 +1,349 @@
 use crate::canvas_items::CanvasItem;
-use crate::state::ExportResizeMode;
-use image::ImageFormat;
+use crate::state::{ExportResize, ExportResizeMode, UiState};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ColorType, ImageFormat};
 use std::io::Cursor;
-use web_sys;
 
 pub struct ImageExporter;
 
 impl ImageExporter {
-    /// リサイズ設定付きでエクスポート
-    pub fn export_image_with_resize(
-        image_bytes: &[u8],
-        rectangles: &[CanvasItem],
-        format: &str,
-        resize_mode: ExportResizeMode,
-        resize_percentage: u32,
-        resize_pixels: u32,
-    ) -> Result<Vec<u8>, String> {
-        if let Ok(img) = image::load_from_memory(image_bytes) {
-            let rgba_img = img.to_rgba8();
-            let original_width = rgba_img.width() as u32;
-            let original_height = rgba_img.height() as u32;
-            let mut width = original_width;
-            let mut height = original_height;
-
-            // リサイズ計算
-            let scale_factor = match resize_mode {
-                ExportResizeMode::Percentage => {
-                    let scale = resize_percentage as f32 / 100.0;
-                    width = (width as f32 * scale).max(1.0) as u32;
-                    height = (height as f32 * scale).max(1.0) as u32;
-                    scale
-                }
-                ExportResizeMode::Pixels => {
-                    // Mpx（メガピクセル）単位で指定された値から画像サイズを計算
-                    // 1 Mpx = 1,000,000 pixels
-                    let target_pixels = (resize_pixels as f64) * 1_000_000.0;
-                    let original_width_f = original_width as f64;
-                    let original_height_f = original_height as f64;
-                    let aspect_ratio = original_height_f / original_width_f;
-
-                    // width * height = target_pixels
-                    // height = width * aspect_ratio
-                    // width * (width * aspect_ratio) = target_pixels
-                    // width^2 = target_pixels / aspect_ratio
-                    let new_width_f = (target_pixels / aspect_ratio).sqrt();
-                    width = new_width_f.max(1.0) as u32;
-                    height = (new_width_f * aspect_ratio).max(1.0) as u32;
-                    width as f32 / original_width as f32
-                }
-            };
+    /// 出力フォーマットに対応するファイルの拡張子
+    pub fn file_extension(format: &str) -> &'static str {
+        match format {
+            "PNG" => "png",
+            "JPEG" => "jpg",
+            "WEBP" => "webp",
+            _ => "bin",
+        }
+    }
+
+    /// 出力フォーマットの MIME タイプ
+    pub fn mime_type(format: &str) -> &'static str {
+        match format {
+            "PNG" => "image/png",
+            "JPEG" => "image/jpeg",
+            "WEBP" => "image/webp",
+            _ => "application/octet-stream",
+        }
+    }
+
+    /// 設定に応じた出力フォーマット（アノテーションのみの場合は透過できるフォーマット）
+    pub fn output_format(ui_state: &UiState) -> &str {
+        if ui_state.export_overlay_only {
+            &ui_state.export_overlay_format
+        } else {
+            &ui_state.export_format
+        }
+    }
+
+    /// 設定に応じた出力サイズ（アノテーションのみの場合は元画像と同じ大きさ）
+    pub fn document_output_size(width: u32, height: u32, ui_state: &UiState) -> (u32, u32) {
+        if ui_state.export_overlay_only {
+            (width, height)
+        } else {
+            Self::output_size(width, height, &ui_state.export_resize)
+        }
+    }
 
-            // リサイズ処理
-            let resized_img = if width != original_width || height != original_height {
-                image::imageops::resize(
-                    &rgba_img,
-                    width,
-                    height,
-                    image::imageops::FilterType::Lanczos3,
+    /// リサイズ設定を適用した出力サイズ
+    pub fn output_size(
+        original_width: u32,
+        original_height: u32,
+        resize: &ExportResize,
+    ) -> (u32, u32) {
+        let original_width_f = original_width as f64;
+        let original_height_f = original_height as f64;
+        let aspect_ratio = original_height_f / original_width_f;
+        let scaled = |scale: f64| {
+            (
+                (original_width_f * scale).max(1.0) as u32,
+                (original_height_f * scale).max(1.0) as u32,
+            )
+        };
+        match resize.mode {
+            ExportResizeMode::Percentage => {
+                let scale = resize.percentage as f32 / 100.0;
+                (
+                    (original_width as f32 * scale).max(1.0) as u32,
+                    (original_height as f32 * scale).max(1.0) as u32,
                 )
-            } else {
-                rgba_img.clone()
-            };
+            }
+            ExportResizeMode::Pixels => {
+                // Mpx（メガピクセル）単位で指定された値から画像サイズを計算
+                // 1 Mpx = 1,000,000 pixels
+                let target_pixels = (resize.megapixels as f64) * 1_000_000.0;
 
-            let mut pixmap =
-                tiny_skia::Pixmap::new(width, height).ok_or("Failed to create pixmap")?;
+                // width * height = target_pixels
+                // height = width * aspect_ratio
+                // width * (width * aspect_ratio) = target_pixels
+                // width^2 = target_pixels / aspect_ratio
+                let new_width_f = (target_pixels / aspect_ratio).sqrt();
+                (
+                    new_width_f.max(1.0) as u32,
+                    (new_width_f * aspect_ratio).max(1.0) as u32,
+                )
+            }
+            ExportResizeMode::Width if resize.keep_aspect => {
+                scaled(resize.width.max(1) as f64 / original_width_f)
+            }
+            ExportResizeMode::Height if resize.keep_aspect => {
+                scaled(resize.height.max(1) as f64 / original_height_f)
+            }
+            // 縦横比を保たない場合は、指定しない側は元のまま
+            ExportResizeMode::Width => (resize.width.max(1), original_height),
+            ExportResizeMode::Height => (original_width, resize.height.max(1)),
+            ExportResizeMode::FitWithin => scaled(
+                (resize.width.max(1) as f64 / original_width_f)
+                    .min(resize.height.max(1) as f64 / original_height_f),
+            ),
+            ExportResizeMode::LongEdge => {
+                let long_edge = original_width_f.max(original_height_f);
+                // 上限より小さい画像は拡大しない
+                scaled((resize.long_edge.max(1) as f64 / long_edge).min(1.0))
+            }
+        }
+    }
 
-            // Copy image data
-            for (i, pixel) in resized_img.pixels().enumerate() {
-                let color = tiny_skia::Color::from_rgba8(pixel[0], pixel[1], pixel[2], pixel[3]);
-                pixmap.pixels_mut()[i] = color.premultiply().to_color_u8();
+    /// アイテムを出力サイズに合わせて描画
+    pub fn draw_items(
+        pixmap: &mut tiny_skia::Pixmap,
+        items: &[CanvasItem],
+        scale_factor: egui::Vec2,
+    ) {
+        for item in items {
+            match item.scale(scale_factor) {
+                CanvasItem::StrokeRect(rect) => rect.draw_on_pixmap(pixmap),
+                CanvasItem::FilledRect(rect) => rect.draw_on_pixmap(pixmap),
+                CanvasItem::Arrow(arrow) => arrow.draw_on_pixmap(pixmap),
+                CanvasItem::Line(line) => line.draw_on_pixmap(pixmap),
+                CanvasItem::Mosaic(mosaic) => mosaic.draw_on_pixmap(pixmap),
             }
+        }
+    }
 
-            // Draw shapes on the pixmap (with scaling)
-            for item in rectangles {
-                let scaled_item = item.scale(scale_factor);
-                match scaled_item {
-                    CanvasItem::StrokeRect(rect) => rect.draw_on_pixmap(&mut pixmap),
-                    CanvasItem::FilledRect(rect) => rect.draw_on_pixmap(&mut pixmap),
-                    CanvasItem::Arrow(arrow) => arrow.draw_on_pixmap(&mut pixmap),
-                    CanvasItem::Line(line) => line.draw_on_pixmap(&mut pixmap),
-                    CanvasItem::Mosaic(mosaic) => {
-                        mosaic.draw_on_pixmap(&mut pixmap, image_bytes);
+    /// 透明なキャンバスにアイテムだけを描画（モザイクは元画像の画素から不透明に作る）
+    pub fn draw_overlay_items(
+        pixmap: &mut tiny_skia::Pixmap,
+        items: &[CanvasItem],
+        source: &image::RgbaImage,
+    ) {
+        let width = pixmap.width();
+        for item in items {
+            let CanvasItem::Mosaic(mosaic) = item else {
+                Self::draw_items(pixmap, std::slice::from_ref(item), egui::Vec2::splat(1.0));
+                continue;
+            };
+            // 通常のエクスポートと同じく、それまでに描いたアイテムを元画像に重ねたものをモザイクにする
+            let x1 = mosaic.x1.max(0.0) as u32;
+            let y1 = mosaic.y1.max(0.0) as u32;
+            let x2 = (mosaic.x2.max(0.0) as u32).min(width);
+            let y2 = (mosaic.y2.max(0.0) as u32).min(pixmap.height());
+            for y in y1..y2 {
+                for x in x1..x2 {
+                    let index = (y * width + x) as usize;
+                    let [r, g, b, a] = source.get_pixel(x, y).0;
+                    let below = tiny_skia::ColorU8::from_rgba(r, g, b, a).premultiply();
+                    let above = pixmap.pixels()[index];
+                    let blend = |top: u8, bottom: u8| {
+                        top + (bottom as u32 * (255 - above.alpha() as u32) / 255) as u8
+                    };
+                    let blended = tiny_skia::PremultipliedColorU8::from_rgba(
+                        blend(above.red(), below.red()),
+                        blend(above.green(), below.green()),
+                        blend(above.blue(), below.blue()),
+                        blend(above.alpha(), below.alpha()),
+                    );
+                    if let Some(blended) = blended {
+                        pixmap.pixels_mut()[index] = blended;
                     }
                 }
             }
+            mosaic.draw_on_pixmap(pixmap);
+        }
+    }
+
+    /// 指定フォーマットでエンコード（JPEG は指定の品質で圧縮し、WebP は可逆圧縮する）
+    pub fn encode(
+        rgba_img: &image::RgbaImage,
+        format: &str,
+        jpeg_quality: u8,
+    ) -> Result<Vec<u8>, String> {
+        let data = match format {
+            "PNG" => {
+                let mut buffer = Vec::new();
+                rgba_img
+                    .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
+                    .map_err(|e| format!("PNG encoding failed: {}", e))?;
+                buffer
+            }
+            "JPEG" => {
+                let mut buffer = Vec::new();
+                JpegEncoder::new_with_quality(&mut buffer, jpeg_quality)
+                    .encode(
+                        rgba_img.as_raw(),
+                        rgba_img.width(),
+                        rgba_img.height(),
+                        ColorType::Rgba8,
+                    )
+                    .map_err(|e| format!("JPEG encoding failed: {}", e))?;
+                buffer
+            }
+            "WEBP" => {
+                let mut buffer = Vec::new();
+                WebPEncoder::new_lossless(&mut buffer)
+                    .encode(
+                        rgba_img.as_raw(),
+                        rgba_img.width(),
+                        rgba_img.height(),
+                        ColorType::Rgba8,
+                    )
+                    .map_err(|e| format!("WebP encoding failed: {}", e))?;
+                buffer
+            }
+            _ => return Err("Unsupported format".to_string()),
+        };
+
+        Ok(data)
+    }
 
-            // Convert pixmap to RgbaImage
-            let mut rgba_img = image::RgbaImage::new(width, height);
-            for (i, pixel) in pixmap.pixels().iter().enumerate() {
-                let x = (i % width as usize) as u32;
-                let y = (i / width as usize) as u32;
-                let color = tiny_skia::Color::from_rgba8(
-                    pixel.red(),
-                    pixel.green(),
-                    pixel.blue(),
-                    pixel.alpha(),
-                );
-                rgba_img.put_pixel(
-                    x,
-                    y,
-                    image::Rgba([
-                        (color.red() * 255.0) as u8,
-                        (color.green() * 255.0) as u8,
-                        (color.blue() * 255.0) as u8,
-                        (color.alpha() * 255.0) as u8,
-                    ]),
-                );
+    /// RGBA 画像を tiny-skia の乗算済みピクセルに変換
+    pub fn rgba_to_pixmap(rgba: &image::RgbaImage) -> Option<tiny_skia::Pixmap> {
+        let size = tiny_skia::IntSize::from_wh(rgba.width(), rgba.height())?;
+        let mut data = rgba.as_raw().clone();
+        Self::premultiply(&mut data);
+        tiny_skia::Pixmap::from_vec(data, size)
+    }
+
+    /// RGBA のピクセル列をその場で乗算済みにする
+    pub fn premultiply(data: &mut [u8]) {
+        for pixel in data.chunks_exact_mut(4) {
+            let alpha = pixel[3] as u16;
+            // 大半を占める不透明なピクセルは乗算しても変わらない
+            if alpha == 255 {
+                continue;
             }
+            for channel in &mut pixel[..3] {
+                *channel = ((*channel as u16 * alpha + 127) / 255) as u8;
+            }
+        }
+    }
 
-            // Encode
-            let data = match format {
-                "PNG" => {
-                    let mut buffer = Vec::new();
-                    rgba_img
-                        .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)
-                        .map_err(|e| format!("PNG encoding failed: {}", e))?;
-                    buffer
-                }
-                "JPEG" => {
-                    let mut buffer = Vec::new();
-                    rgba_img
-                        .write_to(&mut Cursor::new(&mut buffer), ImageFormat::Jpeg)
-                        .map_err(|e| format!("JPEG encoding failed: {}", e))?;
-                    buffer
-                }
-                _ => return Err("Unsupported format".to_string()),
-            };
+    /// 乗算済みピクセルを元に戻す。描画前の乗算済みの値（before）から変わらないピクセルは、
+    /// 元画像の値をそのまま使う
+    pub fn pixmap_to_rgba(
+        pixmap: &tiny_skia::Pixmap,
+        mut original: image::RgbaImage,
+        before: &[u8],
+    ) -> image::RgbaImage {
+        Self::demultiply_changed(&mut original, pixmap.data(), before);
+        original
+    }
 
-            web_sys::console::log_1(&format!("Data length: {}", data.len()).into());
-            Ok(data)
-        } else {
-            Err("Failed to load image".to_string())
+    /// 描画後の乗算済みピクセル（drawn）のうち、描画前（before）から変わったものだけを
+    /// 乗算前の値に戻して dst に書き込む
+    pub fn demultiply_changed(dst: &mut [u8], drawn: &[u8], before: &[u8]) {
+        let pixels = dst
+            .chunks_exact_mut(4)
+            .zip(drawn.chunks_exact(4))
+            .zip(before.chunks_exact(4));
+        for ((dst, drawn), before) in pixels {
+            if drawn == before {
+                continue;
+            }
+            let color =
+                tiny_skia::PremultipliedColorU8::from_rgba(drawn[0], drawn[1], drawn[2], drawn[3])
+                    .map(|color| color.demultiply());
+            if let Some(color) = color {
+                dst.copy_from_slice(&[color.red(), color.green(), color.blue(), color.alpha()]);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transparent_image_round_trips_bit_identical() {
+        let mut rgba = image::RgbaImage::new(4, 3);
+        for (x, y, pixel) in rgba.enumerate_pixels_mut() {
+            // 不透明・半透明・完全に透明（RGB に値が残っているもの）を混ぜる
+            let alpha = [255, 128, 1, 0][x as usize];
+            *pixel = image::Rgba([37 * x as u8 + 3, 91 * y as u8 + 7, 201, alpha]);
+        }
+
+        let pixmap = ImageExporter::rgba_to_pixmap(&rgba).unwrap();
+        let before = pixmap.data().to_vec();
+        let restored = ImageExporter::pixmap_to_rgba(&pixmap, rgba.clone(), &before);
+        assert_eq!(restored.as_raw(), rgba.as_raw());
+
+        let encoded = ImageExporter::encode(&restored, "PNG", 75).unwrap();
+        let decoded = image::load_from_memory(&encoded).unwrap().into_rgba8();
+        assert_eq!(decoded.dimensions(), rgba.dimensions());
+        assert_eq!(decoded.as_raw(), rgba.as_raw());
+    }
+
+    #[test]
+    fn premultiply_matches_tiny_skia() {
+        let rgba = image::RgbaImage::from_fn(256, 1, |x, _| image::Rgba([255, 100, 7, x as u8]));
+        let pixmap = ImageExporter::rgba_to_pixmap(&rgba).unwrap();
+        for (pixel, source) in pixmap.pixels().iter().zip(rgba.pixels()) {
+            let [r, g, b, a] = source.0;
+            let expected = tiny_skia::ColorU8::from_rgba(r, g, b, a).premultiply();
+            assert_eq!(*pixel, expected);
+        }
+    }
+
+    #[test]
+    fn drawn_pixels_are_demultiplied() {
+        let rgba = image::RgbaImage::from_pixel(2, 1, image::Rgba([0, 0, 255, 128]));
+        let mut pixmap = ImageExporter::rgba_to_pixmap(&rgba).unwrap();
+        let before = pixmap.data().to_vec();
+        pixmap.pixels_mut()[1] =
+            tiny_skia::PremultipliedColorU8::from_rgba(255, 0, 0, 255).unwrap();
+        let restored = ImageExporter::pixmap_to_rgba(&pixmap, rgba.clone(), &before);
+        assert_eq!(restored.get_pixel(0, 0), rgba.get_pixel(0, 0));
+        assert_eq!(restored.get_pixel(1, 0).0, [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn width_and_height_modes_resize_one_side() {
+        let resize = |mode, keep_aspect| ExportResize {
+            mode,
+            width: 1000,
+            height: 300,
+            keep_aspect,
+            ..Default::default()
+        };
+        let size = |resize: ExportResize| ImageExporter::output_size(2000, 1500, &resize);
+        assert_eq!(size(resize(ExportResizeMode::Width, true)), (1000, 750));
+        assert_eq!(size(resize(ExportResizeMode::Height, true)), (400, 300));
+        // 縦横比を保たない場合は指定しない側を変えない
+        assert_eq!(size(resize(ExportResizeMode::Width, false)), (1000, 1500));
+        assert_eq!(size(resize(ExportResizeMode::Height, false)), (2000, 300));
+    }
+
+    #[test]
+    fn webp_is_lossless_with_alpha() {
+        let rgba = image::RgbaImage::from_fn(5, 4, |x, y| {
+            image::Rgba([
+                40 * x as u8,
+                60 * y as u8,
+                200,
+                [0, 1, 128, 254, 255][x as usize],
+            ])
+        });
+        let encoded = ImageExporter::encode(&rgba, "WEBP", 75).unwrap();
+        let decoded = image::load_from_memory(&encoded).unwrap().into_rgba8();
+        // 完全に透明なピクセルの RGB は保存されないことがあるので比較しない
+        for (decoded, source) in decoded.pixels().zip(rgba.pixels()) {
+            if source[3] == 0 {
+                assert_eq!(decoded[3], 0);
+            } else {
+                assert_eq!(decoded, source);
+            }
         }
     }
 }
@@ -1,5 +1,16 @@
+pub mod batch_exporter;
+pub mod canvas_encoder;
+pub mod dataset;
 pub mod download_handler;
+pub mod export_job;
+pub mod export_preview;
+pub mod file_name;
 pub mod image_exporter;
 
+pub use batch_exporter::{BATCH_ZIP_FILENAME, BatchExportJob};
+pub use dataset::{COCO_FILENAME, DatasetFormat};
 pub use download_handler::DownloadHandler;
+pub use export_job::{ExportProgress, ImageExportJob};
+pub use export_preview::ExportPreview;
+pub use file_name::{DEFAULT_FILENAME_PATTERN, FILENAME_TOKENS_HELP, FileNameTemplate};
 pub use image_exporter::ImageExporter;
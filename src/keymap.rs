@@ -0,0 +1,187 @@
+use egui::{Key, KeyboardShortcut, Modifiers};
+use serde::{Deserialize, Serialize};
+
+/// eframe のストレージに保存する際のキー
+pub const KEYMAP_STORAGE_KEY: &str = "annoto_keymap";
+
+/// ショートカットで実行できる操作
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShortcutAction {
+    ToolStrokeRect,
+    ToolFilledRect,
+    ToolArrow,
+    ToolLine,
+    ToolMosaic,
+    NudgeLeft,
+    NudgeRight,
+    NudgeUp,
+    NudgeDown,
+    Duplicate,
+    SelectAll,
+    Delete,
+    Cancel,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    ShowHelp,
+}
+
+impl ShortcutAction {
+    pub const ALL: [ShortcutAction; 17] = [
+        ShortcutAction::ToolStrokeRect,
+        ShortcutAction::ToolFilledRect,
+        ShortcutAction::ToolArrow,
+        ShortcutAction::ToolLine,
+        ShortcutAction::ToolMosaic,
+        ShortcutAction::NudgeLeft,
+        ShortcutAction::NudgeRight,
+        ShortcutAction::NudgeUp,
+        ShortcutAction::NudgeDown,
+        ShortcutAction::Duplicate,
+        ShortcutAction::SelectAll,
+        ShortcutAction::Delete,
+        ShortcutAction::Cancel,
+        ShortcutAction::ZoomIn,
+        ShortcutAction::ZoomOut,
+        ShortcutAction::ZoomReset,
+        ShortcutAction::ShowHelp,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ShortcutAction::ToolStrokeRect => "四角形ツール",
+            ShortcutAction::ToolFilledRect => "塗りつぶし四角形ツール",
+            ShortcutAction::ToolArrow => "矢印ツール",
+            ShortcutAction::ToolLine => "直線ツール",
+            ShortcutAction::ToolMosaic => "モザイクツール",
+            ShortcutAction::NudgeLeft => "左へ移動 (Shift で 10px)",
+            ShortcutAction::NudgeRight => "右へ移動 (Shift で 10px)",
+            ShortcutAction::NudgeUp => "上へ移動 (Shift で 10px)",
+            ShortcutAction::NudgeDown => "下へ移動 (Shift で 10px)",
+            ShortcutAction::Duplicate => "複製",
+            ShortcutAction::SelectAll => "すべて選択",
+            ShortcutAction::Delete => "削除",
+            ShortcutAction::Cancel => "ドラッグの取り消し / 選択解除",
+            ShortcutAction::ZoomIn => "拡大",
+            ShortcutAction::ZoomOut => "縮小",
+            ShortcutAction::ZoomReset => "倍率を 100% に戻す",
+            ShortcutAction::ShowHelp => "ショートカット一覧",
+        }
+    }
+
+    fn default_shortcuts(&self) -> Vec<KeyboardShortcut> {
+        let key = |key| KeyboardShortcut::new(Modifiers::NONE, key);
+        let command = |key| KeyboardShortcut::new(Modifiers::COMMAND, key);
+        match self {
+            ShortcutAction::ToolStrokeRect => vec![key(Key::R)],
+            ShortcutAction::ToolFilledRect => vec![key(Key::F)],
+            ShortcutAction::ToolArrow => vec![key(Key::A)],
+            ShortcutAction::ToolLine => vec![key(Key::L)],
+            ShortcutAction::ToolMosaic => vec![key(Key::M)],
+            ShortcutAction::NudgeLeft => vec![key(Key::ArrowLeft)],
+            ShortcutAction::NudgeRight => vec![key(Key::ArrowRight)],
+            ShortcutAction::NudgeUp => vec![key(Key::ArrowUp)],
+            ShortcutAction::NudgeDown => vec![key(Key::ArrowDown)],
+            ShortcutAction::Duplicate => vec![command(Key::D)],
+            ShortcutAction::SelectAll => vec![command(Key::A)],
+            ShortcutAction::Delete => vec![key(Key::Delete), key(Key::Backspace)],
+            ShortcutAction::Cancel => vec![key(Key::Escape)],
+            ShortcutAction::ZoomIn => vec![command(Key::Plus), command(Key::Equals)],
+            ShortcutAction::ZoomOut => vec![command(Key::Minus)],
+            ShortcutAction::ZoomReset => vec![command(Key::Num0)],
+            ShortcutAction::ShowHelp => vec![key(Key::Questionmark), key(Key::F1)],
+        }
+    }
+
+    /// 移動系の操作は Shift 付きでも同じ操作として扱う
+    pub fn is_nudge(&self) -> bool {
+        matches!(
+            self,
+            ShortcutAction::NudgeLeft
+                | ShortcutAction::NudgeRight
+                | ShortcutAction::NudgeUp
+                | ShortcutAction::NudgeDown
+        )
+    }
+}
+
+/// 操作とキーの対応表（ユーザーが変更可能）
+#[derive(Clone, Serialize, Deserialize)]
+pub struct KeyMap {
+    bindings: Vec<(ShortcutAction, KeyboardShortcut)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        let bindings = ShortcutAction::ALL
+            .iter()
+            .flat_map(|action| {
+                action
+                    .default_shortcuts()
+                    .into_iter()
+                    .map(move |shortcut| (*action, shortcut))
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl KeyMap {
+    /// 保存済みのキーマップに存在しない操作へ既定のキーを割り当てる
+    pub fn with_missing_defaults(mut self) -> Self {
+        for action in ShortcutAction::ALL {
+            if !self.bindings.iter().any(|(a, _)| *a == action) {
+                for shortcut in action.default_shortcuts() {
+                    self.bindings.push((action, shortcut));
+                }
+            }
+        }
+        self
+    }
+
+    pub fn shortcuts_for(&self, action: ShortcutAction) -> impl Iterator<Item = &KeyboardShortcut> {
+        self.bindings
+            .iter()
+            .filter(move |(a, _)| *a == action)
+            .map(|(_, shortcut)| shortcut)
+    }
+
+    /// 操作のキーを置き換える（同じキーを使っていた他の操作からは外す）
+    pub fn rebind(&mut self, action: ShortcutAction, shortcut: KeyboardShortcut) {
+        self.bindings
+            .retain(|(a, s)| *a != action && *s != shortcut);
+        self.bindings.push((action, shortcut));
+    }
+
+    /// 押されたショートカットを消費して対応する操作を返す
+    pub fn consume_actions(&self, input: &mut egui::InputState) -> Vec<ShortcutAction> {
+        // 修飾キーの多いものから判定し、Ctrl+A が A より先にマッチするようにする
+        let mut bindings: Vec<_> = self.bindings.iter().collect();
+        bindings.sort_by_key(|(_, shortcut)| std::cmp::Reverse(modifier_count(shortcut.modifiers)));
+
+        let mut actions = Vec::new();
+        for (action, shortcut) in bindings {
+            let count = input.count_and_consume_key(shortcut.modifiers, shortcut.logical_key);
+            // キーリピートは移動系の操作のみ回数分実行する
+            let repeat = if action.is_nudge() {
+                count
+            } else {
+                count.min(1)
+            };
+            actions.extend(std::iter::repeat_n(*action, repeat));
+        }
+        actions
+    }
+}
+
+fn modifier_count(modifiers: Modifiers) -> usize {
+    [
+        modifiers.alt,
+        modifiers.ctrl || modifiers.command,
+        modifiers.shift,
+        modifiers.mac_cmd,
+    ]
+    .iter()
+    .filter(|&&m| m)
+    .count()
+}
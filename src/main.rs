@@ -1,15 +1,33 @@
+// ネイティブではアプリ本体を除いたモジュールだけをビルドし、テストに使う
+#![cfg_attr(not(target_arch = "wasm32"), allow(dead_code, unused_imports))]
+
+#[cfg(target_arch = "wasm32")]
 mod app;
+mod autosave;
 mod canvas_items;
+mod clipboard;
+mod document;
 mod drawing;
 mod drawing_tool;
+mod error;
 mod export;
+mod keymap;
 mod state;
+mod tiled_texture;
 mod touch_handler;
 mod ui;
+mod viewport;
+#[cfg(target_arch = "wasm32")]
 use crate::app::AnnotoApp;
+#[cfg(target_arch = "wasm32")]
 use crate::touch_handler::init_touch_handlers;
+#[cfg(target_arch = "wasm32")]
 use eframe::wasm_bindgen::JsCast as _;
 
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {}
+
+#[cfg(target_arch = "wasm32")]
 fn main() {
     console_error_panic_hook::set_once();
     // Redirect `log` message to `console.log` and friends:
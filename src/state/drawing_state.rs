@@ -1,28 +1,70 @@
 use crate::drawing_tool::DrawingTool;
+use crate::state::tool_style::{StylePreset, ToolPreferences, ToolStyle, ToolStyles};
 use egui;
 
+/// 最近使った色として保持する数
+const MAX_RECENT_COLORS: usize = 8;
+
 pub struct DrawingState {
-    pub zoom: f32,
     pub current_tool: DrawingTool,
     pub drag_start: Option<egui::Pos2>,
-    pub stroke_width: f32,
-    pub stroke_color: egui::Color32,
-    pub fill_color: egui::Color32,
-    pub rounding: u8,
-    pub mosaic_granularity: u8,
+    // ツールごとのスタイル
+    pub styles: ToolStyles,
+    pub presets: Vec<StylePreset>,
+    pub recent_colors: Vec<egui::Color32>,
+    // マグネット吸着（四角形をエッジに合わせる）
+    pub magnetic_snap: bool,
+    pub snap_tolerance: f32,
 }
 
 impl Default for DrawingState {
     fn default() -> Self {
+        Self::from_preferences(ToolPreferences::default())
+    }
+}
+
+impl DrawingState {
+    pub fn from_preferences(preferences: ToolPreferences) -> Self {
         Self {
-            zoom: 100.0,
-            current_tool: DrawingTool::StrokeRect,
+            current_tool: preferences.current_tool,
             drag_start: None,
-            stroke_width: 3.0,
-            stroke_color: egui::Color32::RED,
-            fill_color: egui::Color32::from_rgba_premultiplied(255, 0, 0, 128),
-            rounding: 0,
-            mosaic_granularity: 10,
+            styles: preferences.styles,
+            presets: preferences.presets,
+            recent_colors: preferences.recent_colors,
+            magnetic_snap: preferences.magnetic_snap,
+            snap_tolerance: preferences.snap_tolerance,
+        }
+    }
+
+    pub fn preferences(&self) -> ToolPreferences {
+        ToolPreferences {
+            current_tool: self.current_tool,
+            styles: self.styles.clone(),
+            presets: self.presets.clone(),
+            recent_colors: self.recent_colors.clone(),
+            magnetic_snap: self.magnetic_snap,
+            snap_tolerance: self.snap_tolerance,
         }
     }
+
+    /// 現在のツールのスタイル
+    pub fn style(&self) -> &ToolStyle {
+        self.styles.get(self.current_tool)
+    }
+
+    /// プリセットのツールとスタイルを現在の設定にする
+    pub fn apply_preset(&mut self, index: usize) {
+        if let Some(preset) = self.presets.get(index) {
+            self.current_tool = preset.tool;
+            *self.styles.get_mut(preset.tool) = preset.style;
+        }
+    }
+
+    /// 最近使った色の先頭に追加（不透明度は区別しない）
+    pub fn remember_color(&mut self, color: egui::Color32) {
+        let opaque = color.to_opaque();
+        self.recent_colors.retain(|c| *c != opaque);
+        self.recent_colors.insert(0, opaque);
+        self.recent_colors.truncate(MAX_RECENT_COLORS);
+    }
 }
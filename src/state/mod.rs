@@ -1,10 +1,20 @@
 pub mod drawing_state;
+pub mod notifications;
 pub mod selection_state;
+pub mod tool_style;
 pub mod ui_state;
 
 pub use drawing_state::DrawingState;
+pub use notifications::Notifications;
 pub use selection_state::SelectionState;
-pub use ui_state::{ExportResizeMode, TouchPoint, UiState};
+pub use tool_style::{
+    MOSAIC_GRANULARITY_RANGE, PREFERENCES_STORAGE_KEY, STROKE_WIDTH_RANGE, StylePreset,
+    ToolPreferences, ToolStyle,
+};
+pub use ui_state::{
+    ExportResize, ExportResizeMode, EyedropperTarget, ResampleFilter, TouchPoint, UiState,
+    ViewCommand,
+};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum AppMode {
@@ -0,0 +1,34 @@
+use crate::error::AppError;
+
+/// 通知を表示しておく時間（秒）
+pub const NOTIFICATION_DURATION: f64 = 6.0;
+
+/// 画面の隅に表示するエラー通知
+pub struct Notification {
+    pub message: String,
+    /// 最初に表示した時刻（まだ表示していなければ None）
+    pub shown_at: Option<f64>,
+}
+
+#[derive(Default)]
+pub struct Notifications {
+    pub items: Vec<Notification>,
+}
+
+impl Notifications {
+    pub fn push_error(&mut self, error: &AppError) {
+        self.items.push(Notification {
+            message: error.to_string(),
+            shown_at: None,
+        });
+    }
+
+    /// 表示時間を過ぎた通知を取り除く
+    pub fn expire(&mut self, now: f64) {
+        self.items.retain(|notification| {
+            notification
+                .shown_at
+                .is_none_or(|shown_at| now - shown_at < NOTIFICATION_DURATION)
+        });
+    }
+}
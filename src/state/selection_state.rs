@@ -1,15 +1,44 @@
 use crate::canvas_items::Handle;
 
 pub struct SelectionState {
-    pub selected_item: Option<usize>,
+    pub selected_items: Vec<usize>,
     pub selected_handle: Option<Handle>,
 }
 
 impl Default for SelectionState {
     fn default() -> Self {
         Self {
-            selected_item: None,
+            selected_items: Vec::new(),
             selected_handle: None,
         }
     }
 }
+
+impl SelectionState {
+    /// 1つだけ選択されている場合のアイテム（ハンドル表示やスタイル編集の対象）
+    pub fn selected_item(&self) -> Option<usize> {
+        match self.selected_items.as_slice() {
+            [idx] => Some(*idx),
+            _ => None,
+        }
+    }
+
+    pub fn is_selected(&self, idx: usize) -> bool {
+        self.selected_items.contains(&idx)
+    }
+
+    pub fn select(&mut self, idx: usize) {
+        self.selected_items = vec![idx];
+        self.selected_handle = None;
+    }
+
+    pub fn select_all(&mut self, count: usize) {
+        self.selected_items = (0..count).collect();
+        self.selected_handle = None;
+    }
+
+    pub fn clear(&mut self) {
+        self.selected_items.clear();
+        self.selected_handle = None;
+    }
+}
@@ -0,0 +1,204 @@
+use crate::drawing_tool::DrawingTool;
+use egui;
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+
+/// eframe のストレージに保存する際のキー
+pub const PREFERENCES_STORAGE_KEY: &str = "annoto_preferences";
+
+/// プリセットファイルを判別するための識別子
+const PRESET_FILE_FORMAT: &str = "annoto-presets";
+const PRESET_FILE_VERSION: u32 = 1;
+
+/// 線の太さの範囲（px）
+pub const STROKE_WIDTH_RANGE: RangeInclusive<f32> = 1.0..=50.0;
+/// モザイク粒度の範囲（px）
+pub const MOSAIC_GRANULARITY_RANGE: RangeInclusive<u8> = 1..=100;
+
+/// 描画スタイル（ツールごと・アイテムごとに持つ）
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolStyle {
+    pub stroke_width: f32,
+    pub stroke_color: egui::Color32,
+    pub fill_color: egui::Color32,
+    pub rounding: u8,
+    pub mosaic_granularity: u8,
+    /// 不透明度（0.0〜1.0）
+    pub opacity: f32,
+}
+
+impl Default for ToolStyle {
+    fn default() -> Self {
+        Self {
+            stroke_width: 3.0,
+            stroke_color: egui::Color32::RED,
+            fill_color: egui::Color32::from_rgba_unmultiplied(255, 0, 0, 128),
+            rounding: 0,
+            mosaic_granularity: 10,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl ToolStyle {
+    /// 各値をサイドパネルで設定できる範囲に収める
+    pub fn clamped(self) -> Self {
+        Self {
+            stroke_width: self
+                .stroke_width
+                .clamp(*STROKE_WIDTH_RANGE.start(), *STROKE_WIDTH_RANGE.end()),
+            mosaic_granularity: self.mosaic_granularity.clamp(
+                *MOSAIC_GRANULARITY_RANGE.start(),
+                *MOSAIC_GRANULARITY_RANGE.end(),
+            ),
+            opacity: self.opacity.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+}
+
+/// 各ツールが最後に使ったスタイル
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolStyles {
+    stroke_rect: ToolStyle,
+    filled_rect: ToolStyle,
+    arrow: ToolStyle,
+    line: ToolStyle,
+    mosaic: ToolStyle,
+}
+
+impl ToolStyles {
+    pub fn get(&self, tool: DrawingTool) -> &ToolStyle {
+        match tool {
+            DrawingTool::StrokeRect => &self.stroke_rect,
+            DrawingTool::FilledRect => &self.filled_rect,
+            DrawingTool::Arrow => &self.arrow,
+            DrawingTool::Line => &self.line,
+            DrawingTool::Mosaic => &self.mosaic,
+        }
+    }
+
+    pub fn get_mut(&mut self, tool: DrawingTool) -> &mut ToolStyle {
+        match tool {
+            DrawingTool::StrokeRect => &mut self.stroke_rect,
+            DrawingTool::FilledRect => &mut self.filled_rect,
+            DrawingTool::Arrow => &mut self.arrow,
+            DrawingTool::Line => &mut self.line,
+            DrawingTool::Mosaic => &mut self.mosaic,
+        }
+    }
+}
+
+/// 名前付きのスタイル（例: 「エラー用の赤い矢印」）
+#[derive(Clone, Serialize, Deserialize)]
+pub struct StylePreset {
+    pub name: String,
+    pub tool: DrawingTool,
+    pub style: ToolStyle,
+}
+
+impl StylePreset {
+    pub fn default_presets() -> Vec<StylePreset> {
+        vec![
+            StylePreset {
+                name: "エラー (赤い矢印)".to_string(),
+                tool: DrawingTool::Arrow,
+                style: ToolStyle::default(),
+            },
+            StylePreset {
+                name: "ぼかし 20px".to_string(),
+                tool: DrawingTool::Mosaic,
+                style: ToolStyle {
+                    mosaic_granularity: 20,
+                    ..ToolStyle::default()
+                },
+            },
+        ]
+    }
+}
+
+/// プリセットファイルの形式（チームで共有するための JSON）
+#[derive(Serialize, Deserialize)]
+struct PresetFile {
+    format: String,
+    version: u32,
+    presets: Vec<StylePreset>,
+}
+
+/// セッションをまたいで保持する描画の設定
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ToolPreferences {
+    pub current_tool: DrawingTool,
+    pub styles: ToolStyles,
+    pub presets: Vec<StylePreset>,
+    pub recent_colors: Vec<egui::Color32>,
+    pub magnetic_snap: bool,
+    pub snap_tolerance: f32,
+}
+
+impl Default for ToolPreferences {
+    fn default() -> Self {
+        Self {
+            current_tool: DrawingTool::StrokeRect,
+            styles: ToolStyles::default(),
+            presets: StylePreset::default_presets(),
+            recent_colors: Vec::new(),
+            magnetic_snap: false,
+            snap_tolerance: 8.0,
+        }
+    }
+}
+
+impl ToolPreferences {
+    /// プリセットを JSON ファイルの内容に変換
+    pub fn export_presets(presets: &[StylePreset]) -> String {
+        let file = PresetFile {
+            format: PRESET_FILE_FORMAT.to_string(),
+            version: PRESET_FILE_VERSION,
+            presets: presets.to_vec(),
+        };
+        serde_json::to_string_pretty(&file).unwrap_or_default()
+    }
+
+    /// JSON ファイルからプリセットを読み込む（範囲外の値は設定できる範囲に収める）
+    pub fn import_presets(text: &str) -> Result<Vec<StylePreset>, String> {
+        let file: PresetFile =
+            serde_json::from_str(text).map_err(|e| format!("Invalid preset file: {}", e))?;
+        if file.format != PRESET_FILE_FORMAT {
+            return Err("Not an Annoto preset file".to_string());
+        }
+        Ok(file
+            .presets
+            .into_iter()
+            .map(|preset| StylePreset {
+                style: preset.style.clamped(),
+                ..preset
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn import_clamps_out_of_range_values() {
+        let mut presets = StylePreset::default_presets();
+        presets[0].style.stroke_width = 1000.0;
+        presets[0].style.opacity = -2.0;
+        presets[1].style.mosaic_granularity = 0;
+        let text = ToolPreferences::export_presets(&presets);
+
+        let imported = ToolPreferences::import_presets(&text).unwrap();
+        assert_eq!(imported[0].style.stroke_width, 50.0);
+        assert_eq!(imported[0].style.opacity, 0.0);
+        assert_eq!(imported[1].style.mosaic_granularity, 1);
+        // 範囲内の値はそのまま
+        assert_eq!(imported[1].style.opacity, 1.0);
+        assert_eq!(imported[0].style.mosaic_granularity, 10);
+    }
+}
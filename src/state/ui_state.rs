@@ -1,4 +1,7 @@
-use super::AppMode;
+use super::{AppMode, Notifications};
+use crate::export::DEFAULT_FILENAME_PATTERN;
+use crate::keymap::ShortcutAction;
+use crate::viewport::Viewport;
 use egui;
 
 #[derive(Clone, Copy, Debug)]
@@ -10,18 +13,155 @@ pub struct TouchPoint {
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ExportResizeMode {
     Percentage,
+    /// 画素数（メガピクセル）を指定
     Pixels,
+    /// 幅を指定
+    Width,
+    /// 高さを指定
+    Height,
+    /// 幅×高さの枠に収める
+    FitWithin,
+    /// 長辺が指定値を超えないよう縮小
+    LongEdge,
+}
+
+impl ExportResizeMode {
+    pub const ALL: [ExportResizeMode; 6] = [
+        ExportResizeMode::Percentage,
+        ExportResizeMode::Pixels,
+        ExportResizeMode::Width,
+        ExportResizeMode::Height,
+        ExportResizeMode::FitWithin,
+        ExportResizeMode::LongEdge,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ExportResizeMode::Percentage => "パーセンテージ (%)",
+            ExportResizeMode::Pixels => "ピクセル数",
+            ExportResizeMode::Width => "幅",
+            ExportResizeMode::Height => "高さ",
+            ExportResizeMode::FitWithin => "枠に収める",
+            ExportResizeMode::LongEdge => "長辺の上限",
+        }
+    }
+}
+
+/// リサイズに使う補間方法
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ResampleFilter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Lanczos3,
+}
+
+impl ResampleFilter {
+    pub const ALL: [ResampleFilter; 4] = [
+        ResampleFilter::Nearest,
+        ResampleFilter::Triangle,
+        ResampleFilter::CatmullRom,
+        ResampleFilter::Lanczos3,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ResampleFilter::Nearest => "ニアレストネイバー",
+            ResampleFilter::Triangle => "バイリニア",
+            ResampleFilter::CatmullRom => "バイキュービック (Catmull-Rom)",
+            ResampleFilter::Lanczos3 => "Lanczos3",
+        }
+    }
+
+    pub fn filter_type(&self) -> image::imageops::FilterType {
+        match self {
+            ResampleFilter::Nearest => image::imageops::FilterType::Nearest,
+            ResampleFilter::Triangle => image::imageops::FilterType::Triangle,
+            ResampleFilter::CatmullRom => image::imageops::FilterType::CatmullRom,
+            ResampleFilter::Lanczos3 => image::imageops::FilterType::Lanczos3,
+        }
+    }
+}
+
+/// エクスポート時のリサイズ設定
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ExportResize {
+    pub mode: ExportResizeMode,
+    pub percentage: u32,
+    pub megapixels: f32,
+    pub width: u32,
+    pub height: u32,
+    pub long_edge: u32,
+    /// 幅・高さの指定で縦横比を保つ
+    pub keep_aspect: bool,
+    pub filter: ResampleFilter,
+    /// リサイズ後にアンシャープマスクをかける
+    pub sharpen: bool,
+    /// アンシャープマスクのぼかし半径
+    pub sharpen_sigma: f32,
+}
+
+impl Default for ExportResize {
+    fn default() -> Self {
+        Self {
+            mode: ExportResizeMode::Percentage,
+            percentage: 100,
+            megapixels: 4.0,
+            width: 1920,
+            height: 1080,
+            long_edge: 1920,
+            keep_aspect: true,
+            filter: ResampleFilter::Lanczos3,
+            sharpen: false,
+            sharpen_sigma: 1.0,
+        }
+    }
+}
+
+/// 表示倍率をまとめて変更するコマンド
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewCommand {
+    FitToWindow,
+    FillWidth,
+    ActualSize,
+    // ウィンドウより大きい場合のみ縮小（画像読み込み時）
+    ShrinkToFit,
+}
+
+/// スポイトで取得した色の適用先
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EyedropperTarget {
+    Stroke,
+    Fill,
 }
 
 pub struct UiState {
     pub cursor_pos: Option<egui::Pos2>,
     pub show_export_dialog: bool,
     pub export_format: String,
-    pub export_resize_mode: ExportResizeMode,
-    pub export_resize_percentage: u32,
-    pub export_resize_pixels: u32,
+    pub export_jpeg_quality: u8,
+    /// 画像を含めずアノテーションだけを透過画像で出力する
+    pub export_overlay_only: bool,
+    /// アノテーションのみの出力フォーマット（透過できる PNG か WebP）
+    pub export_overlay_format: String,
+    pub export_resize: ExportResize,
+    pub export_filename_pattern: String,
     pub mode: AppMode,
-    pub pan_offset: egui::Vec2,
+    pub viewport: Viewport,
+    pub view_command: Option<ViewCommand>,
+    pub show_navigator: bool,
+    pub show_shortcut_help: bool,
+    pub recording_shortcut: Option<ShortcutAction>,
+    pub preset_name_input: String,
+    /// 選択中のアイテムに追加するタグの入力欄
+    pub tag_input: String,
+    pub eyedropper: Option<EyedropperTarget>,
+    /// エクスポートのプレビューの表示倍率と、元画像を表示するか
+    pub export_preview_zoom: f32,
+    pub export_preview_original: bool,
+    pub notifications: Notifications,
+    // 閉じる確認中のドキュメント
+    pub pending_close: Option<u64>,
     // タッチ状態管理
     pub touch_points: Vec<TouchPoint>,
     pub prev_touch_points: Vec<TouchPoint>,
@@ -33,11 +173,24 @@ impl Default for UiState {
             cursor_pos: None,
             show_export_dialog: false,
             export_format: "JPEG".to_string(),
-            export_resize_mode: ExportResizeMode::Percentage,
-            export_resize_percentage: 100,
-            export_resize_pixels: 4,
+            export_jpeg_quality: 75,
+            export_overlay_only: false,
+            export_overlay_format: "PNG".to_string(),
+            export_resize: ExportResize::default(),
+            export_filename_pattern: DEFAULT_FILENAME_PATTERN.to_string(),
             mode: AppMode::Drawing,
-            pan_offset: egui::Vec2::ZERO,
+            viewport: Viewport::default(),
+            view_command: None,
+            show_navigator: true,
+            show_shortcut_help: false,
+            recording_shortcut: None,
+            pending_close: None,
+            preset_name_input: String::new(),
+            tag_input: String::new(),
+            eyedropper: None,
+            export_preview_zoom: 0.25,
+            export_preview_original: false,
+            notifications: Notifications::default(),
             touch_points: Vec::new(),
             prev_touch_points: Vec::new(),
         }
@@ -0,0 +1,160 @@
+use image::RgbaImage;
+use image::imageops::{self, FilterType};
+
+/// 縮小版を作るのをやめる長辺のピクセル数
+const MIP_MIN_SIDE: u32 = 1024;
+/// タイルの周囲に重ねて読み込む隣のタイルの画素数
+const TILE_GUTTER: u32 = 1;
+
+/// 1枚のテクスチャに収まる範囲
+struct TextureTile {
+    /// 元画像の座標での範囲
+    rect: egui::Rect,
+    /// テクスチャのうち rect に対応する範囲（周囲の重なりを除いたもの）
+    uv: egui::Rect,
+    texture: egui::TextureHandle,
+}
+
+/// ある縮小率の画像を分割したもの
+struct TextureLevel {
+    /// 元画像に対する倍率
+    scale: f32,
+    tiles: Vec<TextureTile>,
+}
+
+/// GPU のテクスチャサイズの上限を超える画像も表示できるよう、分割して読み込んだテクスチャ
+pub struct TiledTexture {
+    size: egui::Vec2,
+    /// 先頭が原寸で、以降は半分ずつ縮小したもの
+    levels: Vec<TextureLevel>,
+}
+
+impl TiledTexture {
+    pub fn new(ctx: &egui::Context, name: &str, image: &RgbaImage) -> Self {
+        let tile_side = ctx.input(|i| i.max_texture_side) as u32;
+        let size = egui::vec2(image.width() as f32, image.height() as f32);
+        let mut levels = vec![Self::load_level(ctx, name, 0, image, size, tile_side)];
+
+        // ズームアウト時に使う縮小版
+        let mut level_image = None;
+        loop {
+            let previous = level_image.as_ref().unwrap_or(image);
+            let (width, height) = previous.dimensions();
+            if width.max(height) <= MIP_MIN_SIDE {
+                break;
+            }
+            let next = imageops::resize(
+                previous,
+                width.div_ceil(2),
+                height.div_ceil(2),
+                FilterType::Triangle,
+            );
+            levels.push(Self::load_level(
+                ctx,
+                name,
+                levels.len(),
+                &next,
+                size,
+                tile_side,
+            ));
+            level_image = Some(next);
+        }
+        Self { size, levels }
+    }
+
+    fn load_level(
+        ctx: &egui::Context,
+        name: &str,
+        level: usize,
+        image: &RgbaImage,
+        full_size: egui::Vec2,
+        tile_side: u32,
+    ) -> TextureLevel {
+        let (width, height) = image.dimensions();
+        let scale = egui::vec2(full_size.x / width as f32, full_size.y / height as f32);
+        // 隣のタイルと 1 ピクセル重ねて読み込み、境目でも補間に隣の画素が使われるようにする
+        let step = tile_side - 2 * TILE_GUTTER;
+        let mut tiles = Vec::new();
+        for y in (0..height).step_by(step as usize) {
+            for x in (0..width).step_by(step as usize) {
+                let tile_width = step.min(width - x);
+                let tile_height = step.min(height - y);
+                let left = TILE_GUTTER.min(x);
+                let top = TILE_GUTTER.min(y);
+                let right = TILE_GUTTER.min(width - x - tile_width);
+                let bottom = TILE_GUTTER.min(height - y - tile_height);
+                let texture_width = left + tile_width + right;
+                let texture_height = top + tile_height + bottom;
+                let pixels =
+                    imageops::crop_imm(image, x - left, y - top, texture_width, texture_height)
+                        .to_image();
+                let color_image = egui::ColorImage::from_rgba_unmultiplied(
+                    [texture_width as usize, texture_height as usize],
+                    pixels.as_raw(),
+                );
+                let texture = ctx.load_texture(
+                    format!("{}_{}_{}_{}", name, level, x, y),
+                    color_image,
+                    egui::TextureOptions::default(),
+                );
+                let min = egui::pos2(x as f32 * scale.x, y as f32 * scale.y);
+                let tile_size =
+                    egui::vec2(tile_width as f32 * scale.x, tile_height as f32 * scale.y);
+                let uv = egui::Rect::from_min_max(
+                    egui::pos2(
+                        left as f32 / texture_width as f32,
+                        top as f32 / texture_height as f32,
+                    ),
+                    egui::pos2(
+                        (left + tile_width) as f32 / texture_width as f32,
+                        (top + tile_height) as f32 / texture_height as f32,
+                    ),
+                );
+                tiles.push(TextureTile {
+                    rect: egui::Rect::from_min_size(min, tile_size),
+                    uv,
+                    texture,
+                });
+            }
+        }
+        TextureLevel {
+            scale: width as f32 / full_size.x,
+            tiles,
+        }
+    }
+
+    /// 元画像のサイズ（ピクセル）
+    pub fn size_vec2(&self) -> egui::Vec2 {
+        self.size
+    }
+
+    /// 画面上の範囲に画像全体を描く。
+    ///
+    /// 元画像の1ピクセルが画面の何物理ピクセルになるか
+    /// （`screen_rect` の幅 / 元画像の幅 × `pixels_per_point`）を必要な倍率とし、
+    /// 倍率がそれ以上ある縮小版のうち最も小さいものを使う。
+    /// 原寸より拡大して表示する場合など、どの縮小版でも足りなければ原寸を使う
+    pub fn paint(&self, painter: &egui::Painter, screen_rect: egui::Rect) {
+        // 画面の1ピクセルに対して解像度が足りる最も小さい縮小版を使う
+        let needed = screen_rect.width() / self.size.x * painter.ctx().pixels_per_point();
+        let level = self
+            .levels
+            .iter()
+            .rev()
+            .find(|level| level.scale >= needed)
+            .unwrap_or(&self.levels[0]);
+
+        let to_screen = egui::emath::RectTransform::from_to(
+            egui::Rect::from_min_size(egui::Pos2::ZERO, self.size),
+            screen_rect,
+        );
+        let clip_rect = painter.clip_rect();
+        for tile in &level.tiles {
+            let tile_rect = to_screen.transform_rect(tile.rect);
+            if !tile_rect.intersects(clip_rect) {
+                continue;
+            }
+            painter.image(tile.texture.id(), tile_rect, tile.uv, egui::Color32::WHITE);
+        }
+    }
+}
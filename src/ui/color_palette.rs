@@ -0,0 +1,92 @@
+use egui;
+
+/// チームで共通に使う色
+pub const TEAM_PALETTE: [egui::Color32; 8] = [
+    egui::Color32::from_rgb(230, 0, 18),
+    egui::Color32::from_rgb(243, 152, 0),
+    egui::Color32::from_rgb(255, 241, 0),
+    egui::Color32::from_rgb(0, 153, 68),
+    egui::Color32::from_rgb(0, 104, 183),
+    egui::Color32::from_rgb(146, 7, 131),
+    egui::Color32::BLACK,
+    egui::Color32::WHITE,
+];
+
+const SWATCH_SIZE: f32 = 16.0;
+
+/// 色の選択結果
+#[derive(Default)]
+pub struct ColorPickerResponse {
+    /// カラーピッカーで色が変更された
+    pub changed: bool,
+    /// スウォッチから色が選ばれた（最近使った色に追加する）
+    pub picked: bool,
+    /// スポイトボタンが押された
+    pub eyedropper: bool,
+}
+
+/// カラーピッカー・パレット・最近使った色・スポイトをまとめて表示
+///
+/// スウォッチを選んだ場合も現在の不透明度は維持する。
+pub fn color_picker(
+    ui: &mut egui::Ui,
+    color: &mut egui::Color32,
+    recent_colors: &[egui::Color32],
+    eyedropper_active: bool,
+) -> ColorPickerResponse {
+    let mut response = ColorPickerResponse::default();
+    ui.horizontal(|ui| {
+        response.changed = ui.color_edit_button_srgba(color).changed();
+        if ui
+            .selectable_label(eyedropper_active, "スポイト")
+            .on_hover_text("画像から色を取得")
+            .clicked()
+        {
+            response.eyedropper = true;
+        }
+    });
+
+    let mut picked = swatch_row(ui, &TEAM_PALETTE);
+    if !recent_colors.is_empty() {
+        ui.small("最近使った色");
+        picked = picked.or(swatch_row(ui, recent_colors));
+    }
+    if let Some(swatch) = picked {
+        *color = with_alpha_of(swatch, *color);
+        response.changed = true;
+        response.picked = true;
+    }
+    response
+}
+
+/// 色の RGB に、もう一方の色の不透明度を合わせる
+pub fn with_alpha_of(color: egui::Color32, alpha_source: egui::Color32) -> egui::Color32 {
+    let [r, g, b, _] = color.to_srgba_unmultiplied();
+    egui::Color32::from_rgba_unmultiplied(r, g, b, alpha_source.a())
+}
+
+fn swatch_row(ui: &mut egui::Ui, colors: &[egui::Color32]) -> Option<egui::Color32> {
+    let mut picked = None;
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 2.0;
+        for &color in colors {
+            let (rect, response) =
+                ui.allocate_exact_size(egui::Vec2::splat(SWATCH_SIZE), egui::Sense::click());
+            ui.painter().rect_filled(rect, 2.0, color);
+            ui.painter().rect_stroke(
+                rect,
+                2.0,
+                egui::Stroke::new(1.0, ui.visuals().widgets.noninteractive.bg_stroke.color),
+                egui::StrokeKind::Inside,
+            );
+            let [r, g, b, _] = color.to_array();
+            if response
+                .on_hover_text(format!("#{:02X}{:02X}{:02X}", r, g, b))
+                .clicked()
+            {
+                picked = Some(color);
+            }
+        }
+    });
+    picked
+}
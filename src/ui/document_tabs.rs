@@ -0,0 +1,107 @@
+use crate::document::Document;
+use crate::state::UiState;
+use egui;
+
+/// タブのサムネイルの高さ
+const TAB_THUMBNAIL_HEIGHT: f32 = 32.0;
+
+/// タブ上で行われた操作
+pub enum TabAction {
+    Activate(usize),
+    Close(usize),
+}
+
+/// 開いている画像をサムネイル付きのタブとして表示
+pub fn render_document_tabs(
+    ctx: &egui::Context,
+    documents: &[Document],
+    active: usize,
+) -> Option<TabAction> {
+    if documents.is_empty() {
+        return None;
+    }
+    let mut action = None;
+    egui::TopBottomPanel::top("document_tabs").show(ctx, |ui| {
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                for (index, document) in documents.iter().enumerate() {
+                    let frame = egui::Frame::group(ui.style()).fill(if index == active {
+                        ui.visuals().selection.bg_fill
+                    } else {
+                        ui.visuals().faint_bg_color
+                    });
+                    frame.show(ui, |ui| {
+                        let size = document.image_texture.size_vec2();
+                        let thumb_size = size * (TAB_THUMBNAIL_HEIGHT / size.y.max(1.0));
+                        let (thumbnail_rect, thumbnail) =
+                            ui.allocate_exact_size(thumb_size, egui::Sense::click());
+                        document.image_texture.paint(ui.painter(), thumbnail_rect);
+                        // 未保存の変更がある場合は ● を付ける
+                        let title = if document.dirty {
+                            format!("{} ●", document.name())
+                        } else {
+                            document.name().to_string()
+                        };
+                        let (width, height) = document.image_size();
+                        let label =
+                            ui.selectable_label(index == active, title)
+                                .on_hover_text(format!(
+                                    "{}\n{} x {} px, {:.1} KB\n{}{}",
+                                    document.name(),
+                                    width,
+                                    height,
+                                    document.source.size_bytes as f64 / 1024.0,
+                                    document.source.mime_type,
+                                    if document.image.has_alpha {
+                                        " (透過あり)"
+                                    } else {
+                                        ""
+                                    }
+                                ));
+                        if thumbnail.clicked() || label.clicked() {
+                            action = Some(TabAction::Activate(index));
+                        }
+                        if ui.small_button("×").on_hover_text("閉じる").clicked() {
+                            action = Some(TabAction::Close(index));
+                        }
+                    });
+                }
+            });
+        });
+    });
+    action
+}
+
+/// 未保存の画像を閉じる前の確認。閉じることが確定したドキュメントの ID を返す
+pub fn show_close_confirmation(
+    ctx: &egui::Context,
+    ui_state: &mut UiState,
+    documents: &[Document],
+) -> Option<u64> {
+    let id = ui_state.pending_close?;
+    let Some(document) = documents.iter().find(|d| d.id == id) else {
+        ui_state.pending_close = None;
+        return None;
+    };
+
+    let mut confirmed = None;
+    egui::Window::new("未保存の変更")
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.label(format!(
+                "「{}」にはエクスポートしていない変更があります。閉じますか？",
+                document.name()
+            ));
+            ui.horizontal(|ui| {
+                if ui.button("閉じる").clicked() {
+                    confirmed = Some(id);
+                    ui_state.pending_close = None;
+                }
+                if ui.button("キャンセル").clicked() {
+                    ui_state.pending_close = None;
+                }
+            });
+        });
+    confirmed
+}
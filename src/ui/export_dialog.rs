@@ -1,101 +1,375 @@
-use crate::state::{ExportResizeMode, UiState};
+use crate::document::Document;
+use crate::export::{
+    ExportPreview, ExportProgress, FILENAME_TOKENS_HELP, FileNameTemplate, ImageExporter,
+};
+use crate::state::{ExportResize, ExportResizeMode, ResampleFilter, UiState};
 use egui;
 
+/// エクスポートダイアログで選ばれた出力対象
+pub enum ExportRequest {
+    /// 表示中の画像のみ
+    Current,
+    /// 開いているすべての画像を ZIP にまとめる
+    AllAsZip,
+    /// 開いているすべての画像の矩形を COCO JSON で出力
+    CocoJson,
+    /// 表示中の画像の矩形を Pascal VOC XML で出力
+    PascalVoc,
+    /// COCO JSON / Pascal VOC XML から矩形を読み込む
+    ImportAnnotations,
+    /// 実行中のエクスポートを中止
+    Cancel,
+}
+
 pub fn show_export_dialog(
     ctx: &egui::Context,
     ui_state: &mut UiState,
-    image_bytes: Option<&Vec<u8>>,
+    document: Option<&Document>,
+    document_count: usize,
+    progress: Option<&ExportProgress>,
+    preview: &ExportPreview,
     _on_export: impl FnMut(),
-) -> bool {
-    let mut should_export = false;
+) -> Option<ExportRequest> {
+    let mut request = None;
     if ui_state.show_export_dialog {
         let mut open = true;
         egui::Window::new("エクスポート")
             .open(&mut open)
             .show(ctx, |ui| {
-                ui.label("出力フォーマット:");
+                ui.checkbox(
+                    &mut ui_state.export_overlay_only,
+                    "アノテーションのみ（透過画像）",
+                );
+                if ui_state.export_overlay_only {
+                    ui.small("元画像と同じサイズの透明な画像に描画します。モザイクは元画像から作成します。");
+                    ui.horizontal(|ui| {
+                        ui.label("出力フォーマット:");
+                        for (format, label) in [("PNG", "PNG"), ("WEBP", "WebP (可逆)")] {
+                            if ui
+                                .selectable_label(ui_state.export_overlay_format == format, label)
+                                .clicked()
+                            {
+                                ui_state.export_overlay_format = format.to_string();
+                            }
+                        }
+                    });
+                }
+                let overlay_only = ui_state.export_overlay_only;
+                ui.separator();
+
+                ui.add_enabled_ui(!overlay_only, |ui| {
+                    ui.label("出力フォーマット:");
+                    ui.horizontal(|ui| {
+                        if ui
+                            .selectable_label(ui_state.export_format == "PNG", "PNG")
+                            .clicked()
+                        {
+                            ui_state.export_format = "PNG".to_string();
+                        }
+                        if ui
+                            .selectable_label(ui_state.export_format == "JPEG", "JPEG")
+                            .clicked()
+                        {
+                            ui_state.export_format = "JPEG".to_string();
+                        }
+                    });
+                    if ui_state.export_format == "JPEG" {
+                        ui.horizontal(|ui| {
+                            ui.label("品質:");
+                            ui.add(egui::Slider::new(
+                                &mut ui_state.export_jpeg_quality,
+                                1..=100,
+                            ));
+                        });
+                    }
+
+                    ui.separator();
+
+                    ui.label("リサイズ設定:");
+                    render_resize_settings(ui, &mut ui_state.export_resize, document);
+                });
+
+                if let Some(document) = document {
+                    ui.separator();
+                    egui::CollapsingHeader::new("プレビュー")
+                        .default_open(true)
+                        .show(ui, |ui| render_preview(ui, ui_state, document, preview));
+                }
+
+                ui.separator();
+
+                ui.label("ファイル名:");
+                ui.text_edit_singleline(&mut ui_state.export_filename_pattern);
+                ui.small(FILENAME_TOKENS_HELP);
+                if let Some(document) = document {
+                    let filename = FileNameTemplate::for_document(
+                        document,
+                        ui_state,
+                        &FileNameTemplate::today(),
+                    );
+                    ui.label(format!("出力ファイル名: {}", filename));
+                }
+
+                ui.separator();
+
+                // エクスポート中は進捗を表示し、新たな出力は受け付けない
+                if let Some(progress) = progress {
+                    ui.label(&progress.label);
+                    ui.add(egui::ProgressBar::new(progress.fraction).show_percentage());
+                    if ui.button("中止").clicked() {
+                        request = Some(ExportRequest::Cancel);
+                    }
+                    return;
+                }
+
                 ui.horizontal(|ui| {
+                    let can_export = document.is_some();
                     if ui
-                        .selectable_label(ui_state.export_format == "PNG", "PNG")
+                        .add_enabled(can_export, egui::Button::new("エクスポート"))
                         .clicked()
                     {
-                        ui_state.export_format = "PNG".to_string();
+                        request = Some(ExportRequest::Current);
+                    }
+                    if ui.button("キャンセル").clicked() {
+                        ui_state.show_export_dialog = false;
                     }
+                });
+                if document.is_none() {
+                    ui.label("画像をロードしてください。");
+                }
+
+                if document_count >= 2 {
+                    ui.separator();
+                    ui.label("一括エクスポート (ZIP):");
                     if ui
-                        .selectable_label(ui_state.export_format == "JPEG", "JPEG")
+                        .button(format!(
+                            "すべての画像 ({} 枚) をエクスポート",
+                            document_count
+                        ))
                         .clicked()
                     {
-                        ui_state.export_format = "JPEG".to_string();
+                        request = Some(ExportRequest::AllAsZip);
                     }
-                });
+                }
 
                 ui.separator();
-
-                ui.label("リサイズ設定:");
+                ui.label("アノテーションデータ (機械学習用):");
+                ui.small("矩形・塗りつぶし・モザイクの範囲とクラス名を出力します。");
                 ui.horizontal(|ui| {
                     if ui
-                        .selectable_label(
-                            ui_state.export_resize_mode == ExportResizeMode::Percentage,
-                            "パーセンテージ (%)",
-                        )
+                        .add_enabled(document.is_some(), egui::Button::new("COCO JSON"))
+                        .on_hover_text("開いているすべての画像をまとめて出力")
                         .clicked()
                     {
-                        ui_state.export_resize_mode = ExportResizeMode::Percentage;
+                        request = Some(ExportRequest::CocoJson);
                     }
                     if ui
-                        .selectable_label(
-                            ui_state.export_resize_mode == ExportResizeMode::Pixels,
-                            "ピクセル数",
-                        )
+                        .add_enabled(document.is_some(), egui::Button::new("Pascal VOC XML"))
+                        .on_hover_text("表示中の画像を出力")
                         .clicked()
                     {
-                        ui_state.export_resize_mode = ExportResizeMode::Pixels;
+                        request = Some(ExportRequest::PascalVoc);
                     }
-                });
-
-                match ui_state.export_resize_mode {
-                    ExportResizeMode::Percentage => {
-                        ui.horizontal(|ui| {
-                            ui.label("サイズ (%):");
-                            ui.add(
-                                egui::Slider::new(&mut ui_state.export_resize_percentage, 1..=500)
-                                    .text("%"),
-                            );
-                        });
-                    }
-                    ExportResizeMode::Pixels => {
-                        ui.horizontal(|ui| {
-                            ui.label("メガピクセル (Mpx):");
-                            ui.add(
-                                egui::DragValue::new(&mut ui_state.export_resize_pixels)
-                                    .range(1..=30),
-                            );
-                        });
-                    }
-                }
-
-                ui.separator();
-
-                ui.horizontal(|ui| {
-                    let can_export = image_bytes.is_some();
                     if ui
-                        .add_enabled(can_export, egui::Button::new("エクスポート"))
+                        .add_enabled(document.is_some(), egui::Button::new("読み込み..."))
+                        .on_hover_text("COCO JSON / Pascal VOC XML の矩形を追加")
                         .clicked()
                     {
-                        should_export = true;
-                        ui_state.show_export_dialog = false;
-                    }
-                    if ui.button("キャンセル").clicked() {
-                        ui_state.show_export_dialog = false;
+                        request = Some(ExportRequest::ImportAnnotations);
                     }
                 });
-                if image_bytes.is_none() {
-                    ui.label("画像をロードしてください。");
-                }
             });
         if !open {
             ui_state.show_export_dialog = false;
         }
     }
-    should_export
+    request
+}
+
+fn render_resize_settings(
+    ui: &mut egui::Ui,
+    resize: &mut ExportResize,
+    document: Option<&Document>,
+) {
+    ui.horizontal_wrapped(|ui| {
+        for mode in ExportResizeMode::ALL {
+            if ui
+                .selectable_label(resize.mode == mode, mode.label())
+                .clicked()
+            {
+                resize.mode = mode;
+            }
+        }
+    });
+
+    match resize.mode {
+        ExportResizeMode::Percentage => {
+            ui.horizontal(|ui| {
+                ui.label("サイズ (%):");
+                ui.add(egui::Slider::new(&mut resize.percentage, 1..=500).text("%"));
+            });
+        }
+        ExportResizeMode::Pixels => {
+            ui.horizontal(|ui| {
+                ui.label("メガピクセル (Mpx):");
+                ui.add(
+                    egui::DragValue::new(&mut resize.megapixels)
+                        .range(0.01..=100.0)
+                        .speed(0.05)
+                        .max_decimals(2),
+                );
+            });
+        }
+        ExportResizeMode::Width | ExportResizeMode::Height => {
+            ui.checkbox(&mut resize.keep_aspect, "縦横比を保つ");
+            // 指定しない側は入力欄を持たず、縦横比に合わせた値（保たない場合は元画像の値）を表示する。
+            // 入力欄の値は「枠に収める」と共有しているので書き換えない
+            ui.horizontal(|ui| {
+                if resize.mode == ExportResizeMode::Width {
+                    ui.label("幅:");
+                    ui.add(pixel_value(&mut resize.width));
+                } else {
+                    ui.label("高さ:");
+                    ui.add(pixel_value(&mut resize.height));
+                }
+                let Some(document) = document else {
+                    return;
+                };
+                let (width, height) = document.image_size();
+                let (output_width, output_height) =
+                    ImageExporter::output_size(width, height, resize);
+                if resize.mode == ExportResizeMode::Width {
+                    ui.label(format!("高さ: {} px", output_height));
+                } else {
+                    ui.label(format!("幅: {} px", output_width));
+                }
+            });
+        }
+        ExportResizeMode::FitWithin => {
+            ui.horizontal(|ui| {
+                ui.label("幅:");
+                ui.add(pixel_value(&mut resize.width));
+                ui.label("高さ:");
+                ui.add(pixel_value(&mut resize.height));
+            });
+        }
+        ExportResizeMode::LongEdge => {
+            ui.horizontal(|ui| {
+                ui.label("長辺:");
+                ui.add(pixel_value(&mut resize.long_edge));
+            });
+        }
+    }
+
+    ui.horizontal(|ui| {
+        ui.label("補間方法:");
+        egui::ComboBox::from_id_salt("resample_filter")
+            .selected_text(resize.filter.label())
+            .show_ui(ui, |ui| {
+                for filter in ResampleFilter::ALL {
+                    ui.selectable_value(&mut resize.filter, filter, filter.label());
+                }
+            });
+    });
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut resize.sharpen, "リサイズ後にシャープ化");
+        ui.add_enabled(
+            resize.sharpen,
+            egui::Slider::new(&mut resize.sharpen_sigma, 0.3..=3.0).text("半径"),
+        );
+    });
+
+    if let Some(document) = document {
+        let (width, height) = document.image_size();
+        let (output_width, output_height) = ImageExporter::output_size(width, height, resize);
+        ui.label(format!(
+            "出力サイズ: {} x {} px（元: {} x {} px）",
+            output_width, output_height, width, height
+        ));
+    }
+}
+
+fn pixel_value(value: &mut u32) -> egui::DragValue<'_> {
+    egui::DragValue::new(value).range(1..=20000).suffix("px")
+}
+
+/// 出力結果（または比較用の元画像）を出力サイズで表示する
+fn render_preview(
+    ui: &mut egui::Ui,
+    ui_state: &mut UiState,
+    document: &Document,
+    preview: &ExportPreview,
+) {
+    if let Some(error) = preview.error() {
+        ui.colored_label(
+            ui.visuals().error_fg_color,
+            format!("プレビューを作成できませんでした: {}", error),
+        );
+    }
+    let Some(result) = preview.result() else {
+        if !preview.is_rendering() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label("プレビューを作成中...");
+        });
+        return;
+    };
+
+    ui.horizontal(|ui| {
+        ui.selectable_value(&mut ui_state.export_preview_original, false, "処理後");
+        ui.selectable_value(&mut ui_state.export_preview_original, true, "元画像");
+        ui.separator();
+        ui.add(
+            egui::Slider::new(&mut ui_state.export_preview_zoom, 0.05..=4.0)
+                .logarithmic(true)
+                .custom_formatter(|zoom, _| format!("{:.0}%", zoom * 100.0)),
+        );
+        if ui.button("1:1").clicked() {
+            ui_state.export_preview_zoom = 1.0;
+        }
+    });
+
+    let (width, height) = result.size;
+    let display_size = egui::vec2(width as f32, height as f32) * ui_state.export_preview_zoom;
+    egui::ScrollArea::both()
+        .max_height(320.0)
+        .max_width(480.0)
+        .show(ui, |ui| {
+            let (rect, _) = ui.allocate_exact_size(display_size, egui::Sense::hover());
+            // 元画像も出力と同じ大きさで表示し、切り替えて見比べられるようにする
+            if ui_state.export_preview_original {
+                document.image_texture.paint(ui.painter(), rect);
+            } else {
+                result.texture.paint(ui.painter(), rect);
+            }
+        });
+
+    let status = if preview.is_rendering() {
+        " (更新中...)"
+    } else {
+        ""
+    };
+    // 縮小して作成したプレビューのファイルサイズは見積もり
+    let file_size = if result.exact_size {
+        format!("ファイルサイズ: {}", format_file_size(result.encoded_bytes))
+    } else {
+        format!(
+            "推定ファイルサイズ: 約 {}",
+            format_file_size(result.encoded_bytes)
+        )
+    };
+    ui.label(format!(
+        "{} x {} px, {}{}",
+        width, height, file_size, status
+    ));
+}
+
+fn format_file_size(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    }
 }
@@ -1,7 +1,21 @@
+pub mod color_palette;
+pub mod document_tabs;
 pub mod export_dialog;
+pub mod navigator_panel;
+pub mod notifications;
+pub mod preset_panel;
+pub mod restore_dialog;
+pub mod shortcut_help;
 pub mod side_panel;
 pub mod top_panel;
 
-pub use export_dialog::show_export_dialog;
+pub use color_palette::with_alpha_of;
+pub use document_tabs::{TabAction, render_document_tabs, show_close_confirmation};
+pub use export_dialog::{ExportRequest, show_export_dialog};
+pub use navigator_panel::render_navigator_panel;
+pub use notifications::show_notifications;
+pub use preset_panel::PresetFileAction;
+pub use restore_dialog::show_restore_dialog;
+pub use shortcut_help::show_shortcut_help;
 pub use side_panel::render_side_panel;
 pub use top_panel::render_top_panel;
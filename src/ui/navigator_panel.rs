@@ -0,0 +1,78 @@
+use crate::canvas_items::CanvasItem;
+use crate::drawing::ItemRenderer;
+use crate::state::UiState;
+use crate::tiled_texture::TiledTexture;
+use crate::viewport::{MAX_ZOOM, MIN_ZOOM, Viewport};
+use egui;
+
+/// サムネイルの最大の高さ
+const THUMBNAIL_MAX_HEIGHT: f32 = 200.0;
+
+pub fn render_navigator_panel(
+    ctx: &egui::Context,
+    ui_state: &mut UiState,
+    texture: Option<&TiledTexture>,
+    rectangles: &[CanvasItem],
+) {
+    if !ui_state.show_navigator {
+        return;
+    }
+    let Some(texture) = texture else {
+        return;
+    };
+    egui::SidePanel::right("navigator_panel")
+        .default_width(220.0)
+        .show(ctx, |ui| {
+            ui.label("ナビゲーター");
+
+            // サムネイルを表示する領域を計算
+            let image_size = texture.size_vec2();
+            let thumb_scale =
+                (ui.available_width() / image_size.x).min(THUMBNAIL_MAX_HEIGHT / image_size.y);
+            let (response, painter) =
+                ui.allocate_painter(image_size * thumb_scale, egui::Sense::click_and_drag());
+            let thumb_viewport = Viewport {
+                zoom: thumb_scale * 100.0,
+                pan: egui::Vec2::ZERO,
+                canvas_rect: response.rect,
+                image_size,
+            };
+
+            texture.paint(&painter, response.rect);
+            ItemRenderer::render_existing_items(ui, rectangles, &thumb_viewport);
+
+            // 現在表示中の範囲
+            let visible = ui_state.viewport.visible_image_rect();
+            if visible.is_positive() {
+                painter.rect_stroke(
+                    thumb_viewport.image_rect_to_screen(visible),
+                    0.0,
+                    egui::Stroke::new(2.0, egui::Color32::from_rgb(255, 200, 0)),
+                    egui::StrokeKind::Middle,
+                );
+            }
+
+            // クリックまたはドラッグした位置を表示の中心にする
+            let jump_to = response
+                .interact_pointer_pos()
+                .filter(|_| response.clicked() || response.dragged());
+            if let Some(pos) = jump_to {
+                ui_state
+                    .viewport
+                    .center_on(thumb_viewport.screen_to_image(pos));
+            }
+
+            ui.add_space(8.0);
+            let mut zoom = ui_state.viewport.zoom;
+            if ui
+                .add(
+                    egui::Slider::new(&mut zoom, MIN_ZOOM..=MAX_ZOOM)
+                        .logarithmic(true)
+                        .suffix("%"),
+                )
+                .changed()
+            {
+                ui_state.viewport.zoom_at_center(zoom);
+            }
+        });
+}
@@ -0,0 +1,37 @@
+use crate::state::Notifications;
+
+/// 画面右下に通知を積み重ねて表示する
+pub fn show_notifications(ctx: &egui::Context, notifications: &mut Notifications) {
+    if notifications.items.is_empty() {
+        return;
+    }
+    let now = ctx.input(|i| i.time);
+    notifications.expire(now);
+
+    let mut dismissed = None;
+    egui::Area::new(egui::Id::new("notifications"))
+        .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-12.0, -12.0))
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            for (index, notification) in notifications.items.iter_mut().enumerate() {
+                notification.shown_at.get_or_insert(now);
+                egui::Frame::popup(ui.style())
+                    .fill(ui.visuals().extreme_bg_color)
+                    .stroke(egui::Stroke::new(1.0, ui.visuals().error_fg_color))
+                    .show(ui, |ui| {
+                        ui.set_max_width(360.0);
+                        ui.horizontal(|ui| {
+                            ui.colored_label(ui.visuals().error_fg_color, &notification.message);
+                            if ui.small_button("×").on_hover_text("閉じる").clicked() {
+                                dismissed = Some(index);
+                            }
+                        });
+                    });
+            }
+        });
+    if let Some(index) = dismissed {
+        notifications.items.remove(index);
+    }
+    // 表示時間が過ぎたら消えるよう再描画を続ける
+    ctx.request_repaint_after(std::time::Duration::from_secs(1));
+}
@@ -0,0 +1,77 @@
+use crate::state::{DrawingState, StylePreset, UiState};
+use egui;
+
+/// プリセットファイルの読み書き
+pub enum PresetFileAction {
+    Import,
+    Export,
+}
+
+/// 名前付きスタイルプリセットの一覧と保存
+pub fn render_presets(
+    ui: &mut egui::Ui,
+    drawing_state: &mut DrawingState,
+    ui_state: &mut UiState,
+) -> Option<PresetFileAction> {
+    let mut action = None;
+    ui.label("プリセット");
+
+    let mut apply = None;
+    let mut remove = None;
+    for (index, preset) in drawing_state.presets.iter().enumerate() {
+        ui.horizontal(|ui| {
+            if ui
+                .button(&preset.name)
+                .on_hover_text(preset.tool.label())
+                .clicked()
+            {
+                apply = Some(index);
+            }
+            if ui.small_button("×").on_hover_text("削除").clicked() {
+                remove = Some(index);
+            }
+        });
+    }
+    if let Some(index) = apply {
+        drawing_state.apply_preset(index);
+    }
+    if let Some(index) = remove {
+        drawing_state.presets.remove(index);
+    }
+
+    // 現在のツールのスタイルを名前を付けて保存
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut ui_state.preset_name_input)
+                .hint_text("プリセット名")
+                .desired_width(100.0),
+        );
+        let name = ui_state.preset_name_input.trim().to_string();
+        if ui
+            .add_enabled(!name.is_empty(), egui::Button::new("保存"))
+            .clicked()
+        {
+            let preset = StylePreset {
+                name: name.clone(),
+                tool: drawing_state.current_tool,
+                style: *drawing_state.style(),
+            };
+            // 同じ名前のプリセットは上書きする
+            match drawing_state.presets.iter_mut().find(|p| p.name == name) {
+                Some(existing) => *existing = preset,
+                None => drawing_state.presets.push(preset),
+            }
+            ui_state.preset_name_input.clear();
+        }
+    });
+
+    ui.horizontal(|ui| {
+        if ui.button("読み込み").clicked() {
+            action = Some(PresetFileAction::Import);
+        }
+        if ui.button("書き出し").clicked() {
+            action = Some(PresetFileAction::Export);
+        }
+    });
+    action
+}
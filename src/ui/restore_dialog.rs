@@ -0,0 +1,32 @@
+use crate::autosave::RestoredSession;
+use egui;
+
+/// 前回のセッションを復元するか確認する。復元するなら Some(true)、破棄するなら Some(false)
+pub fn show_restore_dialog(ctx: &egui::Context, session: &RestoredSession) -> Option<bool> {
+    let mut answer = None;
+    egui::Window::new("前回のセッションを復元")
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label("前回の作業内容が保存されています。復元しますか？");
+            ui.add_space(4.0);
+            for document in &session.snapshot.documents {
+                ui.label(format!(
+                    "・{} (アイテム {} 個)",
+                    document.source.name,
+                    document.rectangles.len()
+                ));
+            }
+            ui.add_space(4.0);
+            ui.horizontal(|ui| {
+                if ui.button("復元").clicked() {
+                    answer = Some(true);
+                }
+                if ui.button("破棄").clicked() {
+                    answer = Some(false);
+                }
+            });
+        });
+    answer
+}
@@ -0,0 +1,74 @@
+use crate::keymap::{KeyMap, ShortcutAction};
+use crate::state::UiState;
+use egui;
+
+pub fn show_shortcut_help(ctx: &egui::Context, ui_state: &mut UiState, keymap: &mut KeyMap) {
+    if !ui_state.show_shortcut_help {
+        ui_state.recording_shortcut = None;
+        return;
+    }
+
+    // キー割り当ての変更中は次に押されたキーを割り当てる
+    if let Some(action) = ui_state.recording_shortcut {
+        let pressed = ctx.input_mut(|i| {
+            let pressed = i.events.iter().find_map(|event| match event {
+                egui::Event::Key {
+                    key,
+                    pressed: true,
+                    modifiers,
+                    ..
+                } => Some(egui::KeyboardShortcut::new(*modifiers, *key)),
+                _ => None,
+            });
+            if pressed.is_some() {
+                i.events
+                    .retain(|event| !matches!(event, egui::Event::Key { .. }));
+            }
+            pressed
+        });
+        if let Some(shortcut) = pressed {
+            if shortcut.logical_key != egui::Key::Escape {
+                keymap.rebind(action, shortcut);
+            }
+            ui_state.recording_shortcut = None;
+        }
+    }
+
+    let mut open = true;
+    egui::Window::new("ショートカット一覧")
+        .open(&mut open)
+        .show(ctx, |ui| {
+            egui::Grid::new("shortcut_grid")
+                .striped(true)
+                .show(ui, |ui| {
+                    for action in ShortcutAction::ALL {
+                        ui.label(action.label());
+                        let keys = keymap
+                            .shortcuts_for(action)
+                            .map(|shortcut| ctx.format_shortcut(shortcut))
+                            .collect::<Vec<_>>()
+                            .join(" / ");
+                        if ui_state.recording_shortcut == Some(action) {
+                            ui.label("キーを押してください (Esc で中止)");
+                        } else {
+                            ui.label(keys);
+                        }
+                        if ui.button("変更").clicked() {
+                            ui_state.recording_shortcut = Some(action);
+                        }
+                        ui.end_row();
+                    }
+                });
+            ui.separator();
+            // クリップボード操作はブラウザの標準キーで固定
+            ui.label("コピー / 切り取り / 貼り付け: Ctrl+C / Ctrl+X / Ctrl+V");
+            ui.add_space(8.0);
+            if ui.button("既定に戻す").clicked() {
+                *keymap = KeyMap::default();
+                ui_state.recording_shortcut = None;
+            }
+        });
+    if !open {
+        ui_state.show_shortcut_help = false;
+    }
+}
@@ -1,154 +1,292 @@
-use crate::canvas_items::CanvasItem;
+use crate::canvas_items::{CanvasItem, ItemMetadata, PropertyChange, StyleChange};
 use crate::drawing_tool::DrawingTool;
-use crate::state::{AppMode, DrawingState, UiState};
+use crate::state::{
+    AppMode, DrawingState, EyedropperTarget, MOSAIC_GRANULARITY_RANGE, STROKE_WIDTH_RANGE,
+    ToolStyle, UiState,
+};
+use crate::ui::color_palette::color_picker;
+use crate::ui::preset_panel::{PresetFileAction, render_presets};
 use egui;
 
 pub fn render_side_panel(
     ctx: &egui::Context,
     drawing_state: &mut DrawingState,
-    ui_state: &UiState,
-    selected_item: Option<usize>,
-    rectangles: &[CanvasItem],
-    mut on_update_selected: impl FnMut(),
-) {
+    ui_state: &mut UiState,
+    selected_item: Option<&CanvasItem>,
+    mut on_style_change: impl FnMut(StyleChange),
+    mut on_property_change: impl FnMut(PropertyChange),
+) -> Option<PresetFileAction> {
     if ui_state.mode != AppMode::Drawing {
-        return;
+        return None;
     }
+    let mut preset_action = None;
     egui::SidePanel::left("side_panel").show(ctx, |ui| {
         ui.label("描画ツール");
-        if ui
-            .selectable_label(
-                matches!(drawing_state.current_tool, DrawingTool::StrokeRect),
-                "四角形",
-            )
-            .clicked()
-        {
-            drawing_state.current_tool = DrawingTool::StrokeRect;
-        }
-        if ui
-            .selectable_label(
-                matches!(drawing_state.current_tool, DrawingTool::FilledRect),
-                "塗りつぶし四角形",
-            )
-            .clicked()
-        {
-            drawing_state.current_tool = DrawingTool::FilledRect;
-        }
-        if ui
-            .selectable_label(
-                matches!(drawing_state.current_tool, DrawingTool::Arrow),
-                "矢印",
-            )
-            .clicked()
-        {
-            drawing_state.current_tool = DrawingTool::Arrow;
-        }
-        if ui
-            .selectable_label(
-                matches!(drawing_state.current_tool, DrawingTool::Line),
-                "直線",
-            )
-            .clicked()
-        {
-            drawing_state.current_tool = DrawingTool::Line;
-        }
-        if ui
-            .selectable_label(
-                matches!(drawing_state.current_tool, DrawingTool::Mosaic),
-                "モザイク",
-            )
-            .clicked()
-        {
-            drawing_state.current_tool = DrawingTool::Mosaic;
+        for tool in DrawingTool::ALL {
+            if ui
+                .selectable_label(drawing_state.current_tool == tool, tool.label())
+                .clicked()
+            {
+                drawing_state.current_tool = tool;
+            }
         }
         ui.add_space(16.0);
 
-        let tool_type = if let Some(idx) = selected_item {
-            if let Some(item) = rectangles.get(idx) {
-                match item {
-                    CanvasItem::StrokeRect(_) => "StrokeRect",
-                    CanvasItem::FilledRect(_) => "FilledRect",
-                    CanvasItem::Arrow(_) => "Arrow",
-                    CanvasItem::Line(_) => "Line",
-                    CanvasItem::Mosaic(_) => "Mosaic",
-                }
-            } else {
-                ""
+        // 選択中のアイテムがあればそのアイテム自身、なければ現在のツールのスタイルを編集する
+        let (style_tool, mut style) = match selected_item {
+            Some(item) => {
+                ui.label(format!("選択中のアイテム ({})", item.tool().label()));
+                (item.tool(), item_style(item))
             }
-        } else {
-            match drawing_state.current_tool {
-                DrawingTool::StrokeRect => "StrokeRect",
-                DrawingTool::FilledRect => "FilledRect",
-                DrawingTool::Arrow => "Arrow",
-                DrawingTool::Line => "Line",
-                DrawingTool::Mosaic => "Mosaic",
+            None => {
+                let tool = drawing_state.current_tool;
+                ui.label(format!("{}のスタイル", tool.label()));
+                (tool, *drawing_state.styles.get(tool))
             }
         };
+        let mut changes = Vec::new();
+        let mut picked_color = None;
 
-        if matches!(tool_type, "StrokeRect" | "Line") {
+        if matches!(style_tool, DrawingTool::StrokeRect | DrawingTool::Line) {
             ui.label("線の太さ:");
             if ui
                 .add(
-                    egui::DragValue::new(&mut drawing_state.stroke_width)
-                        .range(1..=50)
+                    egui::DragValue::new(&mut style.stroke_width)
+                        .range(STROKE_WIDTH_RANGE)
                         .suffix("px"),
                 )
                 .changed()
             {
-                on_update_selected();
+                changes.push(StyleChange::StrokeWidth(style.stroke_width));
             }
             ui.add_space(16.0);
         }
 
-        if matches!(tool_type, "Mosaic") {
+        if matches!(style_tool, DrawingTool::Mosaic) {
             ui.label("モザイク粒度:");
             if ui
                 .add(
-                    egui::DragValue::new(&mut drawing_state.mosaic_granularity)
-                        .range(1..=100)
+                    egui::DragValue::new(&mut style.mosaic_granularity)
+                        .range(MOSAIC_GRANULARITY_RANGE)
                         .suffix("px"),
                 )
                 .changed()
             {
-                on_update_selected();
+                changes.push(StyleChange::MosaicGranularity(style.mosaic_granularity));
             }
             ui.add_space(16.0);
         }
 
-        if matches!(tool_type, "StrokeRect" | "Arrow" | "Line" | "FilledRect") {
+        if matches!(
+            style_tool,
+            DrawingTool::StrokeRect
+                | DrawingTool::Arrow
+                | DrawingTool::Line
+                | DrawingTool::FilledRect
+        ) {
             ui.label("線の色:");
-            if ui
-                .color_edit_button_srgba(&mut drawing_state.stroke_color)
-                .changed()
-            {
-                on_update_selected();
+            let response = color_picker(
+                ui,
+                &mut style.stroke_color,
+                &drawing_state.recent_colors,
+                ui_state.eyedropper == Some(EyedropperTarget::Stroke),
+            );
+            if response.changed {
+                changes.push(StyleChange::StrokeColor(style.stroke_color));
+            }
+            if response.picked {
+                picked_color = Some(style.stroke_color);
+            }
+            if response.eyedropper {
+                toggle_eyedropper(ui_state, EyedropperTarget::Stroke);
             }
         }
 
-        if matches!(tool_type, "FilledRect") {
+        if matches!(style_tool, DrawingTool::FilledRect) {
             ui.add_space(16.0);
             ui.label("塗りつぶし色:");
-            if ui
-                .color_edit_button_srgba(&mut drawing_state.fill_color)
-                .changed()
-            {
-                on_update_selected();
+            let response = color_picker(
+                ui,
+                &mut style.fill_color,
+                &drawing_state.recent_colors,
+                ui_state.eyedropper == Some(EyedropperTarget::Fill),
+            );
+            if response.changed {
+                changes.push(StyleChange::FillColor(style.fill_color));
+            }
+            if response.picked {
+                picked_color = Some(style.fill_color);
+            }
+            if response.eyedropper {
+                toggle_eyedropper(ui_state, EyedropperTarget::Fill);
             }
         }
 
-        if matches!(tool_type, "StrokeRect" | "FilledRect") {
+        if matches!(
+            style_tool,
+            DrawingTool::StrokeRect | DrawingTool::FilledRect
+        ) {
             ui.add_space(16.0);
             ui.label("角の丸め:");
             if ui
                 .add(
-                    egui::DragValue::new(&mut drawing_state.rounding)
+                    egui::DragValue::new(&mut style.rounding)
                         .range(0..=255)
                         .suffix("px"),
                 )
                 .changed()
             {
-                on_update_selected();
+                changes.push(StyleChange::Rounding(style.rounding));
+            }
+        }
+
+        // モザイクは隠す目的のため不透明度を持たない
+        if !matches!(style_tool, DrawingTool::Mosaic) {
+            ui.add_space(16.0);
+            ui.label("不透明度:");
+            let mut percent = style.opacity * 100.0;
+            if ui
+                .add(egui::Slider::new(&mut percent, 0.0..=100.0).suffix("%"))
+                .changed()
+            {
+                style.opacity = percent / 100.0;
+                changes.push(StyleChange::Opacity(style.opacity));
+            }
+        }
+
+        // 選択中のアイテムへの変更は呼び出し元で適用し、ツールのスタイルには反映しない
+        if selected_item.is_some() {
+            for change in changes {
+                on_style_change(change);
+            }
+        } else {
+            *drawing_state.styles.get_mut(style_tool) = style;
+        }
+        if let Some(color) = picked_color {
+            drawing_state.remember_color(color);
+        }
+
+        if let Some(item) = selected_item {
+            ui.add_space(16.0);
+            render_metadata(
+                ui,
+                item.metadata(),
+                &mut ui_state.tag_input,
+                &mut on_property_change,
+            );
+        }
+
+        // マグネット吸着は新規に描く四角形にのみ適用
+        if selected_item.is_none()
+            && matches!(
+                style_tool,
+                DrawingTool::StrokeRect | DrawingTool::FilledRect | DrawingTool::Mosaic
+            )
+        {
+            ui.add_space(16.0);
+            ui.checkbox(&mut drawing_state.magnetic_snap, "マグネット吸着");
+            ui.add_enabled_ui(drawing_state.magnetic_snap, |ui| {
+                ui.label("吸着範囲:");
+                ui.add(
+                    egui::DragValue::new(&mut drawing_state.snap_tolerance)
+                        .range(1.0..=50.0)
+                        .suffix("px"),
+                );
+            });
+        }
+
+        ui.add_space(16.0);
+        ui.separator();
+        preset_action = render_presets(ui, drawing_state, ui_state);
+    });
+    preset_action
+}
+
+/// アイテムの現在のスタイル（持たない項目は既定値）
+fn item_style(item: &CanvasItem) -> ToolStyle {
+    let default = ToolStyle::default();
+    ToolStyle {
+        stroke_width: match item {
+            CanvasItem::Mosaic(_) => default.stroke_width,
+            _ => item.get_stroke_width().unwrap_or(default.stroke_width),
+        },
+        stroke_color: item.get_stroke_color().unwrap_or(default.stroke_color),
+        fill_color: item.get_fill_color().unwrap_or(default.fill_color),
+        rounding: item.get_rounding().unwrap_or(default.rounding),
+        mosaic_granularity: match item {
+            CanvasItem::Mosaic(mosaic) => mosaic.get_granularity(),
+            _ => default.mosaic_granularity,
+        },
+        opacity: item.get_opacity().unwrap_or(default.opacity),
+    }
+}
+
+fn toggle_eyedropper(ui_state: &mut UiState, target: EyedropperTarget) {
+    ui_state.eyedropper = if ui_state.eyedropper == Some(target) {
+        None
+    } else {
+        Some(target)
+    };
+}
+
+/// 選択中のアイテムのクラス名・メモ・タグ
+fn render_metadata(
+    ui: &mut egui::Ui,
+    metadata: &ItemMetadata,
+    tag_input: &mut String,
+    on_property_change: &mut impl FnMut(PropertyChange),
+) {
+    ui.label("クラス名:");
+    let mut label = metadata.label.clone();
+    if ui
+        .add(egui::TextEdit::singleline(&mut label).hint_text("object"))
+        .changed()
+    {
+        on_property_change(PropertyChange::Label(label));
+    }
+
+    ui.label("メモ:");
+    let mut note = metadata.note.clone();
+    if ui
+        .add(egui::TextEdit::multiline(&mut note).desired_rows(2))
+        .changed()
+    {
+        on_property_change(PropertyChange::Note(note));
+    }
+
+    ui.label("タグ:");
+    let mut removed = None;
+    ui.horizontal_wrapped(|ui| {
+        for (i, tag) in metadata.tags.iter().enumerate() {
+            if ui
+                .small_button(format!("{} ×", tag))
+                .on_hover_text("タグを削除")
+                .clicked()
+            {
+                removed = Some(i);
+            }
+        }
+    });
+    if let Some(i) = removed {
+        let mut tags = metadata.tags.clone();
+        tags.remove(i);
+        on_property_change(PropertyChange::Tags(tags));
+    }
+    ui.horizontal(|ui| {
+        let response = ui.add(
+            egui::TextEdit::singleline(tag_input)
+                .hint_text("新しいタグ")
+                .desired_width(100.0),
+        );
+        let submitted = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+        let tag = tag_input.trim().to_string();
+        if (submitted || ui.button("追加").clicked()) && !tag.is_empty() {
+            if !metadata.tags.contains(&tag) {
+                let mut tags = metadata.tags.clone();
+                tags.push(tag);
+                on_property_change(PropertyChange::Tags(tags));
             }
+            tag_input.clear();
         }
     });
 }
@@ -1,12 +1,8 @@
-use crate::state::{DrawingState, UiState};
+use crate::state::{UiState, ViewCommand};
+use crate::viewport::{MAX_ZOOM, MIN_ZOOM};
 use egui;
 
-pub fn render_top_panel(
-    ctx: &egui::Context,
-    drawing_state: &mut DrawingState,
-    ui_state: &mut UiState,
-    on_open_file: impl FnOnce(),
-) {
+pub fn render_top_panel(ctx: &egui::Context, ui_state: &mut UiState, on_open_file: impl FnOnce()) {
     egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
         egui::MenuBar::new().ui(ui, |ui| {
             ui.menu_button("File", |ui| {
@@ -17,13 +13,37 @@ pub fn render_top_panel(
                     ui_state.show_export_dialog = true;
                 }
             });
+            ui.menu_button("View", |ui| {
+                if ui.button("ウィンドウに合わせる").clicked() {
+                    ui_state.view_command = Some(ViewCommand::FitToWindow);
+                }
+                if ui.button("幅に合わせる").clicked() {
+                    ui_state.view_command = Some(ViewCommand::FillWidth);
+                }
+                if ui.button("100%").clicked() {
+                    ui_state.view_command = Some(ViewCommand::ActualSize);
+                }
+                ui.separator();
+                ui.checkbox(&mut ui_state.show_navigator, "ナビゲーター");
+            });
+            ui.menu_button("Help", |ui| {
+                if ui.button("ショートカット一覧").clicked() {
+                    ui_state.show_shortcut_help = true;
+                }
+            });
             ui.add_space(16.0);
             ui.label("倍率:");
-            ui.add(
-                egui::DragValue::new(&mut drawing_state.zoom)
-                    .range(1.0..=500.0)
-                    .suffix("%"),
-            );
+            let mut zoom = ui_state.viewport.zoom;
+            if ui
+                .add(
+                    egui::DragValue::new(&mut zoom)
+                        .range(MIN_ZOOM..=MAX_ZOOM)
+                        .suffix("%"),
+                )
+                .changed()
+            {
+                ui_state.viewport.zoom_at_center(zoom);
+            }
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 if let Some(pos) = ui_state.cursor_pos {
                     ui.label(format!("X: {:.0}, Y: {:.0}", pos.x, pos.y));
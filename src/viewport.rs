@@ -0,0 +1,214 @@
+use crate::state::ViewCommand;
+use egui::{Pos2, Rect, Vec2};
+
+pub const MIN_ZOOM: f32 = 1.0;
+pub const MAX_ZOOM: f32 = 500.0;
+
+/// 画像座標とスクリーン座標の対応（倍率・パン・表示領域）
+///
+/// egui の描画処理には依存せず、座標変換だけを扱う。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Viewport {
+    /// 表示倍率（%）
+    pub zoom: f32,
+    /// キャンバス左上から画像左上までのオフセット（スクリーン座標）
+    pub pan: Vec2,
+    /// 画像を表示する領域（スクリーン座標）
+    pub canvas_rect: Rect,
+    /// 画像のサイズ（画像座標）
+    pub image_size: Vec2,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            zoom: 100.0,
+            pan: Vec2::ZERO,
+            canvas_rect: Rect::NOTHING,
+            image_size: Vec2::ZERO,
+        }
+    }
+}
+
+impl Viewport {
+    /// 画像1ピクセルあたりのスクリーン上の長さ
+    pub fn scale(&self) -> f32 {
+        self.zoom / 100.0
+    }
+
+    /// 画像の原点のスクリーン座標
+    pub fn origin(&self) -> Pos2 {
+        self.canvas_rect.min + self.pan
+    }
+
+    pub fn image_to_screen(&self, pos: Pos2) -> Pos2 {
+        self.origin() + pos.to_vec2() * self.scale()
+    }
+
+    pub fn screen_to_image(&self, pos: Pos2) -> Pos2 {
+        ((pos - self.origin()) / self.scale()).to_pos2()
+    }
+
+    pub fn image_rect_to_screen(&self, rect: Rect) -> Rect {
+        Rect::from_min_max(
+            self.image_to_screen(rect.min),
+            self.image_to_screen(rect.max),
+        )
+    }
+
+    /// 画像座標の長さ（線の太さなど）をスクリーン上の長さに変換
+    pub fn image_len_to_screen(&self, len: f32) -> f32 {
+        len * self.scale()
+    }
+
+    /// スクリーン上の移動量を画像座標の移動量に変換
+    pub fn screen_delta_to_image(&self, delta: Vec2) -> Vec2 {
+        delta / self.scale()
+    }
+
+    /// 画像全体が表示される矩形（スクリーン座標）
+    pub fn image_screen_rect(&self) -> Rect {
+        Rect::from_min_size(self.origin(), self.image_size * self.scale())
+    }
+
+    /// キャンバスに表示されている画像の範囲（画像座標）
+    pub fn visible_image_rect(&self) -> Rect {
+        let visible = Rect::from_min_max(
+            self.screen_to_image(self.canvas_rect.min),
+            self.screen_to_image(self.canvas_rect.max),
+        );
+        visible.intersect(Rect::from_min_size(Pos2::ZERO, self.image_size))
+    }
+
+    pub fn pan_by(&mut self, delta: Vec2) {
+        self.pan += delta;
+    }
+
+    /// 指定したスクリーン座標が動かないように倍率を変更
+    pub fn zoom_at(&mut self, anchor: Pos2, new_zoom: f32) {
+        let new_zoom = new_zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+
+        // アンカーの画像座標
+        let anchor_image_pos = self.screen_to_image(anchor).to_vec2();
+
+        // ズーム前後での画像座標の変化を計算
+        let zoom_change = new_zoom / self.zoom;
+
+        // アンカーが画面上の同じ位置に留まるようにパンを調整
+        self.pan += anchor_image_pos * (1.0 - zoom_change) * self.scale();
+        self.zoom = new_zoom;
+    }
+
+    /// キャンバス中央を基準に倍率を変更
+    pub fn zoom_at_center(&mut self, new_zoom: f32) {
+        self.zoom_at(self.canvas_rect.center(), new_zoom);
+    }
+
+    /// 画像上の位置がキャンバス中央に来るようにパンを設定
+    pub fn center_on(&mut self, image_pos: Pos2) {
+        let target = self.canvas_rect.center();
+        self.pan += target - self.image_to_screen(image_pos);
+    }
+
+    pub fn apply_command(&mut self, command: ViewCommand) {
+        if self.image_size.x <= 0.0 || self.image_size.y <= 0.0 {
+            return;
+        }
+        let canvas = self.canvas_rect.size();
+        let fit = (canvas.x / self.image_size.x).min(canvas.y / self.image_size.y) * 100.0;
+        let zoom = match command {
+            ViewCommand::FitToWindow => fit,
+            ViewCommand::FillWidth => canvas.x / self.image_size.x * 100.0,
+            ViewCommand::ActualSize => 100.0,
+            ViewCommand::ShrinkToFit => fit.min(100.0),
+        };
+        self.zoom = zoom.clamp(MIN_ZOOM, MAX_ZOOM);
+
+        // ウィンドウより小さい場合は中央に、大きい場合は左上に合わせる
+        let scaled_size = self.image_size * self.scale();
+        self.pan = ((canvas - scaled_size) / 2.0).max(Vec2::ZERO);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn viewport(zoom: f32, pan: Vec2) -> Viewport {
+        Viewport {
+            zoom,
+            pan,
+            canvas_rect: Rect::from_min_size(Pos2::new(50.0, 30.0), Vec2::new(800.0, 600.0)),
+            image_size: Vec2::new(400.0, 200.0),
+        }
+    }
+
+    fn assert_pos_eq(a: Pos2, b: Pos2) {
+        // 倍率 1% では画像座標の誤差がスクリーン座標の 100 倍になる
+        assert!((a - b).length() < 1e-2, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn screen_image_round_trip() {
+        let image_pos = Pos2::new(123.5, 45.25);
+        for zoom in [1.0, 25.0, 100.0, 333.0, 500.0] {
+            for pan in [Vec2::ZERO, Vec2::new(-120.0, 35.5), Vec2::new(300.0, -80.0)] {
+                let viewport = viewport(zoom, pan);
+                let screen = viewport.image_to_screen(image_pos);
+                assert_pos_eq(viewport.screen_to_image(screen), image_pos);
+            }
+        }
+    }
+
+    #[test]
+    fn zoom_at_keeps_anchor_fixed() {
+        let anchor = Pos2::new(310.0, 220.0);
+        for (from, to) in [(100.0, 250.0), (250.0, 40.0), (100.0, 1000.0)] {
+            let mut viewport = viewport(from, Vec2::new(-40.0, 15.0));
+            let image_pos = viewport.screen_to_image(anchor);
+            viewport.zoom_at(anchor, to);
+            assert_pos_eq(viewport.image_to_screen(image_pos), anchor);
+        }
+    }
+
+    #[test]
+    fn zoom_at_clamps_zoom() {
+        let mut viewport = viewport(100.0, Vec2::ZERO);
+        viewport.zoom_at(Pos2::new(100.0, 100.0), 10_000.0);
+        assert_eq!(viewport.zoom, MAX_ZOOM);
+        viewport.zoom_at(Pos2::new(100.0, 100.0), 0.0);
+        assert_eq!(viewport.zoom, MIN_ZOOM);
+    }
+
+    #[test]
+    fn fit_to_window_shows_whole_image_centered() {
+        let mut viewport = viewport(37.0, Vec2::new(12.0, -5.0));
+        viewport.apply_command(ViewCommand::FitToWindow);
+        // 400x200 を 800x600 に収めると幅で決まる
+        assert_eq!(viewport.zoom, 200.0);
+        assert_eq!(viewport.pan, Vec2::new(0.0, 100.0));
+        assert_eq!(
+            viewport.image_screen_rect().center(),
+            viewport.canvas_rect.center()
+        );
+    }
+
+    #[test]
+    fn fill_width_matches_canvas_width() {
+        let mut viewport = viewport(100.0, Vec2::ZERO);
+        viewport.image_size = Vec2::new(400.0, 1000.0);
+        viewport.apply_command(ViewCommand::FillWidth);
+        assert_eq!(viewport.zoom, 200.0);
+        // 縦にはみ出す場合は上端に合わせる
+        assert_eq!(viewport.pan, Vec2::ZERO);
+        assert_eq!(viewport.image_screen_rect().width(), 800.0);
+    }
+
+    #[test]
+    fn actual_size_uses_100_percent() {
+        let mut viewport = viewport(250.0, Vec2::new(-300.0, -300.0));
+        viewport.apply_command(ViewCommand::ActualSize);
+        assert_eq!(viewport.zoom, 100.0);
+        assert_eq!(viewport.pan, Vec2::new(200.0, 200.0));
+    }
+}